@@ -6,8 +6,9 @@ mod imp {
     use adw::subclass::prelude::*;
     use adw::Application;
 
-    use crate::startup_disk::startup_disk_library;
+    use crate::settings;
     use crate::window::StartupDiskWindow;
+    use startup_disk::startup_disk_library;
 
     #[derive(Default)]
     pub struct StartupDiskApplication;
@@ -34,7 +35,11 @@ mod imp {
             let window = if let Some(window) = app.active_window() {
                 window
             } else {
-                let window = StartupDiskWindow::new(&*app, startup_disk_library.is_supported());
+                let window = StartupDiskWindow::new(
+                    &*app,
+                    startup_disk_library.is_supported(),
+                    settings::settings().as_ref(),
+                );
                 window.upcast()
             };
 
@@ -46,13 +51,16 @@ mod imp {
     impl AdwApplicationImpl for StartupDiskApplication {}
 }
 
-use adw::gio::{self, ActionEntry, ActionGroup, ActionMap};
+use adw::gio::{self, ActionEntry, ActionGroup, ActionMap, SimpleAction};
 use adw::glib;
 use adw::gtk;
 use adw::prelude::*;
 use adw::{AboutDialog, Application};
 
 use crate::config;
+use crate::preferences::StartupDiskPreferences;
+use crate::settings;
+use crate::window::StartupDiskWindow;
 
 glib::wrapper! {
     pub struct StartupDiskApplication(ObjectSubclass<imp::StartupDiskApplication>)
@@ -73,11 +81,38 @@ impl StartupDiskApplication {
         let about_action = ActionEntry::builder("about")
             .activate(move |app: &Self, _, _| app.show_about())
             .build();
-        self.add_action_entries([about_action]);
+        // Re-reads the boot candidates of the active window
+        let refresh_action = ActionEntry::builder("refresh")
+            .activate(move |app: &Self, _, _| {
+                if let Some(window) = app.active_window().and_downcast::<StartupDiskWindow>() {
+                    window.refresh();
+                }
+            })
+            .build();
+        let preferences_action = ActionEntry::builder("preferences")
+            .activate(move |app: &Self, _, _| app.show_preferences())
+            .build();
+        self.add_action_entries([about_action, refresh_action, preferences_action]);
+
+        // Without an installed schema there is nothing to store preferences in
+        if settings::settings().is_none() {
+            self.lookup_action("preferences")
+                .and_downcast::<SimpleAction>()
+                .unwrap()
+                .set_enabled(false);
+        }
 
         // Keyboard shortcuts
         self.set_accels_for_action("app.quit", &["<primary>q"]);
         self.set_accels_for_action("window.close", &["<primary>w"]);
+        self.set_accels_for_action("app.refresh", &["<primary>r", "F5"]);
+        self.set_accels_for_action("app.preferences", &["<primary>comma"]);
+    }
+
+    fn show_preferences(&self) {
+        if let Some(settings) = settings::settings() {
+            StartupDiskPreferences::new(&settings).present(self.active_window().as_ref());
+        }
     }
 
     fn show_about(&self) {
@@ -1,19 +1,40 @@
 // SPDX-License-Identifier: MIT
 
 pub mod object;
+pub mod row;
 
 mod imp {
     use adw::glib::{self, subclass::InitializingObject, Binding};
-    use adw::gtk::{self, CompositeTemplate, Label};
+    use adw::gtk::{self, CompositeTemplate, Image, Label, PopoverMenu};
+    use adw::prelude::*;
     use adw::subclass::prelude::*;
-    use std::cell::RefCell;
+    use std::cell::{OnceCell, RefCell};
 
     #[derive(Default, CompositeTemplate)]
     #[template(resource = "/org/startup-disk/StartupDisk/boot_candidate.ui")]
     pub struct BootCandidateWidget {
+        #[template_child]
+        pub icon: TemplateChild<Image>,
         #[template_child]
         pub name: TemplateChild<Label>,
+        #[template_child]
+        pub os_label: TemplateChild<Label>,
+        #[template_child]
+        pub other_volumes: TemplateChild<Label>,
+        #[template_child]
+        pub startup_disk: TemplateChild<Label>,
+        #[template_child]
+        pub next_boot: TemplateChild<Label>,
+        #[template_child]
+        pub current: TemplateChild<Label>,
+        #[template_child]
+        pub vg_uuid: TemplateChild<Label>,
+        #[template_child]
+        pub part_uuid: TemplateChild<Label>,
+        #[template_child]
+        pub volume_count: TemplateChild<Label>,
 
+        pub context_menu: OnceCell<PopoverMenu>,
         pub bindings: RefCell<Vec<Binding>>,
     }
 
@@ -32,15 +53,37 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for BootCandidateWidget {}
+    impl ObjectImpl for BootCandidateWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let context_menu = super::attach_context_menu(&*self.obj());
+            self.context_menu.set(context_menu).unwrap();
+        }
+
+        fn dispose(&self) {
+            if let Some(context_menu) = self.context_menu.get() {
+                context_menu.unparent();
+            }
+        }
+    }
+
     impl WidgetImpl for BootCandidateWidget {}
     impl BoxImpl for BootCandidateWidget {}
 }
 
-use adw::glib;
-use adw::gtk::{Accessible, Box, Buildable, ConstraintTarget, Orientable, Widget};
+use adw::gdk;
+use adw::gio::{Menu, MenuItem, MenuModel};
+use adw::glib::{self, Binding};
+use adw::gtk::{
+    Accessible, Box, Buildable, ConstraintTarget, GestureClick, GestureLongPress, Image, Label,
+    Orientable, PopoverMenu, Widget,
+};
 use adw::prelude::*;
 use adw::subclass::prelude::*;
+use gettextrs::gettext;
+use startup_disk::ngettext_f;
+use std::cell::RefCell;
 
 use self::object::BootCandidateObject;
 
@@ -57,23 +100,48 @@ impl BootCandidateWidget {
 
     // Creates bindings to object
     pub fn bind(&self, object: &BootCandidateObject) {
-        // Borrow bindings vector
-        let mut bindings = self.imp().bindings.borrow_mut();
+        let imp = self.imp();
+        let mut bindings = imp.bindings.borrow_mut();
 
-        // Create binding for the name label
-        let name_label = self.imp().name.get();
-        let name_binding = object
-            .bind_property("name", &name_label, "label")
+        let children = CandidateChildren {
+            icon: imp.icon.get(),
+            name: imp.name.get(),
+            os_label: imp.os_label.get(),
+            other_volumes: imp.other_volumes.get(),
+            startup_disk: imp.startup_disk.get(),
+            next_boot: imp.next_boot.get(),
+            current: imp.current.get(),
+        };
+        bind_candidate(
+            object,
+            &children,
+            imp.context_menu.get().unwrap(),
+            &mut bindings,
+        );
+
+        // Create bindings for the details popover
+        for (property, label) in [
+            ("vg-uuid", imp.vg_uuid.get()),
+            ("part-uuid", imp.part_uuid.get()),
+        ] {
+            let binding = object
+                .bind_property(property, &label, "label")
+                .sync_create()
+                .build();
+            bindings.push(binding);
+        }
+        let volume_count_binding = object
+            .bind_property("volume-count", &imp.volume_count.get(), "label")
+            .transform_to(|_, count: u32| Some(ngettext_f("{n} volume", "{n} volumes", count, &[])))
             .sync_create()
             .build();
-        bindings.push(name_binding);
+        bindings.push(volume_count_binding);
     }
 
     // Removes bindings
     pub fn unbind(&self) {
-        for binding in self.imp().bindings.borrow_mut().drain(..) {
-            binding.unbind();
-        }
+        let imp = self.imp();
+        unbind_candidate(&imp.bindings, imp.context_menu.get().unwrap());
     }
 }
 
@@ -82,3 +150,121 @@ impl Default for BootCandidateWidget {
         Self::new()
     }
 }
+
+/// Children that the tile and the list row both show for a boot candidate
+struct CandidateChildren {
+    icon: Image,
+    name: Label,
+    os_label: Label,
+    other_volumes: Label,
+    startup_disk: Label,
+    next_boot: Label,
+    current: Label,
+}
+
+/// Binds `children` to `object` and points the context menu at it
+fn bind_candidate(
+    object: &BootCandidateObject,
+    children: &CandidateChildren,
+    context_menu: &PopoverMenu,
+    bindings: &mut Vec<Binding>,
+) {
+    for (property, label) in [
+        ("name", &children.name),
+        ("os-label", &children.os_label),
+        ("other-volumes", &children.other_volumes),
+    ] {
+        let binding = object
+            .bind_property(property, label, "label")
+            .sync_create()
+            .build();
+        bindings.push(binding);
+    }
+    let icon_binding = object
+        .bind_property("icon-name", &children.icon, "icon-name")
+        .sync_create()
+        .build();
+    bindings.push(icon_binding);
+
+    // Show the volumes line only if the group has other volumes
+    let other_volumes_visible_binding = object
+        .bind_property("other-volumes", &children.other_volumes, "visible")
+        .transform_to(|_, other_volumes: String| Some(!other_volumes.is_empty()))
+        .sync_create()
+        .build();
+    bindings.push(other_volumes_visible_binding);
+
+    // Show the status badges only when they apply. The startup disk badge
+    // stays on the default while another candidate is selected.
+    for (property, label) in [
+        ("is-default", &children.startup_disk),
+        ("is-next", &children.next_boot),
+        ("is-current", &children.current),
+    ] {
+        let binding = object
+            .bind_property(property, label, "visible")
+            .sync_create()
+            .build();
+        bindings.push(binding);
+    }
+
+    context_menu.set_menu_model(Some(&context_menu_model(object)));
+}
+
+/// Removes the bindings and the context menu set up by [`bind_candidate`]
+fn unbind_candidate(bindings: &RefCell<Vec<Binding>>, context_menu: &PopoverMenu) {
+    for binding in bindings.borrow_mut().drain(..) {
+        binding.unbind();
+    }
+    context_menu.set_menu_model(None::<&Menu>);
+}
+
+/// Adds a context menu to `widget`, opened by right click or long press
+fn attach_context_menu(widget: &impl IsA<Widget>) -> PopoverMenu {
+    let context_menu = PopoverMenu::from_model(None::<&MenuModel>);
+    context_menu.set_parent(widget);
+    context_menu.set_has_arrow(false);
+
+    let click = GestureClick::new();
+    click.set_button(gdk::BUTTON_SECONDARY);
+    click.connect_pressed(glib::clone!(
+        #[weak]
+        context_menu,
+        move |_, _, x, y| {
+            popup_at(&context_menu, x, y);
+        }
+    ));
+    widget.add_controller(click);
+
+    let long_press = GestureLongPress::new();
+    long_press.set_touch_only(true);
+    long_press.connect_pressed(glib::clone!(
+        #[weak]
+        context_menu,
+        move |_, x, y| {
+            popup_at(&context_menu, x, y);
+        }
+    ));
+    widget.add_controller(long_press);
+
+    context_menu
+}
+
+fn popup_at(context_menu: &PopoverMenu, x: f64, y: f64) {
+    context_menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+    context_menu.popup();
+}
+
+/// Returns the context menu, with its actions pointed at this candidate
+fn context_menu_model(object: &BootCandidateObject) -> Menu {
+    let menu = Menu::new();
+    let start_up_once = MenuItem::new(Some(&gettext("Start Up Once")), None);
+    start_up_once
+        .set_action_and_target_value(Some("win.start-up-once"), Some(&object.vg_uuid().into()));
+    menu.append_item(&start_up_once);
+    let edit_label = MenuItem::new(Some(&gettext("Edit Label…")), None);
+    edit_label.set_action_and_target_value(Some("win.edit-label"), Some(&object.vg_uuid().into()));
+    menu.append_item(&edit_label);
+
+    menu
+}
@@ -4,7 +4,7 @@ mod imp {
     use adw::glib;
     use adw::prelude::*;
     use adw::subclass::prelude::*;
-    use asahi_bless::BootCandidate;
+    use startup_disk::BootCandidate;
     use std::cell::RefCell;
 
     #[derive(glib::Properties, Default)]
@@ -12,6 +12,28 @@ mod imp {
     pub struct BootCandidateObject {
         #[property(get, set)]
         name: RefCell<String>,
+        #[property(get, set)]
+        vg_uuid: RefCell<String>,
+        #[property(get, set)]
+        part_uuid: RefCell<String>,
+        /// Names of the volumes in the group other than the one the name comes from
+        #[property(get, set)]
+        other_volumes: RefCell<String>,
+        /// Label of the operating system on the candidate
+        #[property(get, set)]
+        os_label: RefCell<String>,
+        #[property(get, set)]
+        icon_name: RefCell<String>,
+        /// Number of volumes in the group, for the details popover
+        #[property(get, set)]
+        volume_count: RefCell<u32>,
+        #[property(get, set)]
+        is_default: RefCell<bool>,
+        #[property(get, set)]
+        is_next: RefCell<bool>,
+        /// Whether this is the running system, regardless of the NVRAM
+        #[property(get, set)]
+        is_current: RefCell<bool>,
 
         pub boot_candidate: RefCell<Option<BootCandidate>>,
     }
@@ -37,21 +59,63 @@ mod imp {
     }
 }
 
-use crate::startup_disk::get_vg_name;
 use adw::glib::{self, subclass::types::ObjectSubclassIsExt};
-use asahi_bless::BootCandidate;
+use startup_disk::BootCandidate;
+use startup_disk::{copy_candidate, get_vg_name, Label, OsType};
 
 glib::wrapper! {
     pub struct BootCandidateObject(ObjectSubclass<imp::BootCandidateObject>);
 }
 
 impl BootCandidateObject {
-    pub fn new(candidate: BootCandidate) -> Self {
-        let object: BootCandidateObject = glib::Object::builder()
-            .property("name", get_vg_name(&candidate.volumes))
-            .build();
-        *object.imp().boot_candidate.borrow_mut() = Some(candidate);
+    pub fn new(candidate: BootCandidate, label: Option<&Label>) -> Self {
+        let object: BootCandidateObject = glib::Object::new();
+        object.update(candidate, label);
 
         object
     }
+
+    /// Replaces the wrapped boot candidate, such as after a rescan, and applies
+    /// the name and icon the user gave it
+    pub fn update(&self, candidate: BootCandidate, label: Option<&Label>) {
+        let label = label.cloned().unwrap_or_default();
+        let name = label
+            .name
+            .as_deref()
+            .unwrap_or(get_vg_name(&candidate.volumes));
+        // Under a custom name, the volume group name is listed with the others
+        let other_volumes: Vec<&str> = candidate
+            .volumes
+            .iter()
+            .map(|v| v.name.as_str())
+            .filter(|&volume| volume != name)
+            .collect();
+
+        let os_type = OsType::classify(&candidate);
+
+        self.set_name(name);
+        self.set_vg_uuid(candidate.vg_uuid.to_string());
+        self.set_part_uuid(candidate.part_uuid.to_string());
+        self.set_other_volumes(other_volumes.join(", "));
+        self.set_os_label(os_type.label());
+        self.set_volume_count(candidate.volumes.len() as u32);
+        self.set_icon_name(label.icon.as_deref().unwrap_or(os_type.icon_name()));
+        *self.imp().boot_candidate.borrow_mut() = Some(candidate);
+    }
+
+    /// Returns a copy of the wrapped boot candidate
+    pub fn boot_candidate(&self) -> BootCandidate {
+        copy_candidate(self.imp().boot_candidate.borrow().as_ref().unwrap())
+    }
+
+    /// Returns whether this object wraps the given boot candidate
+    pub fn matches(&self, candidate: &BootCandidate) -> bool {
+        self.imp()
+            .boot_candidate
+            .borrow()
+            .as_ref()
+            .is_some_and(|cand| {
+                cand.part_uuid == candidate.part_uuid && cand.vg_uuid == candidate.vg_uuid
+            })
+    }
 }
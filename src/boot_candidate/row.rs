@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MIT
+
+mod imp {
+    use adw::glib::{self, subclass::InitializingObject, Binding};
+    use adw::gtk::{self, CompositeTemplate, Image, Label, PopoverMenu};
+    use adw::prelude::*;
+    use adw::subclass::prelude::*;
+    use std::cell::{OnceCell, RefCell};
+
+    #[derive(Default, CompositeTemplate)]
+    #[template(resource = "/org/startup-disk/StartupDisk/boot_candidate_row.ui")]
+    pub struct BootCandidateRow {
+        #[template_child]
+        pub icon: TemplateChild<Image>,
+        #[template_child]
+        pub name: TemplateChild<Label>,
+        #[template_child]
+        pub os_label: TemplateChild<Label>,
+        #[template_child]
+        pub other_volumes: TemplateChild<Label>,
+        #[template_child]
+        pub startup_disk: TemplateChild<Label>,
+        #[template_child]
+        pub next_boot: TemplateChild<Label>,
+        #[template_child]
+        pub current: TemplateChild<Label>,
+
+        pub context_menu: OnceCell<PopoverMenu>,
+        pub bindings: RefCell<Vec<Binding>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for BootCandidateRow {
+        const NAME: &'static str = "StartupDiskBootCandidateRow";
+        type Type = super::BootCandidateRow;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for BootCandidateRow {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let context_menu = super::super::attach_context_menu(&*self.obj());
+            self.context_menu.set(context_menu).unwrap();
+        }
+
+        fn dispose(&self) {
+            if let Some(context_menu) = self.context_menu.get() {
+                context_menu.unparent();
+            }
+        }
+    }
+
+    impl WidgetImpl for BootCandidateRow {}
+    impl BoxImpl for BootCandidateRow {}
+}
+
+use adw::glib;
+use adw::gtk::{Accessible, Box, Buildable, ConstraintTarget, Orientable, Widget};
+use adw::subclass::prelude::*;
+
+use super::object::BootCandidateObject;
+use super::{bind_candidate, unbind_candidate, CandidateChildren};
+
+glib::wrapper! {
+    /// Compact presentation of a boot candidate, for the list view
+    pub struct BootCandidateRow(ObjectSubclass<imp::BootCandidateRow>)
+        @extends Box, Widget,
+        @implements Accessible, Buildable, ConstraintTarget, Orientable;
+}
+
+impl BootCandidateRow {
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+
+    // Creates bindings to object
+    pub fn bind(&self, object: &BootCandidateObject) {
+        let imp = self.imp();
+        let children = CandidateChildren {
+            icon: imp.icon.get(),
+            name: imp.name.get(),
+            os_label: imp.os_label.get(),
+            other_volumes: imp.other_volumes.get(),
+            startup_disk: imp.startup_disk.get(),
+            next_boot: imp.next_boot.get(),
+            current: imp.current.get(),
+        };
+        bind_candidate(
+            object,
+            &children,
+            imp.context_menu.get().unwrap(),
+            &mut imp.bindings.borrow_mut(),
+        );
+    }
+
+    // Removes bindings
+    pub fn unbind(&self) {
+        let imp = self.imp();
+        unbind_candidate(&imp.bindings, imp.context_menu.get().unwrap());
+    }
+}
+
+impl Default for BootCandidateRow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,592 @@
+// SPDX-License-Identifier: MIT
+
+use adw::glib;
+use clap::Subcommand;
+use gettextrs::gettext;
+use serde::Serialize;
+use startup_disk::BootCandidate;
+use uuid::Uuid;
+
+use startup_disk::{
+    escalate_if_needed, get_vg_name, gettext_f, is_same_candidate, ngettext_f,
+    startup_disk_library, Backup, Error, Label, Labels, Operation, OsType, Result,
+};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List the available startup disks
+    List,
+    /// Show the current startup disk
+    Get {
+        /// Show the startup disk for the next boot only
+        #[arg(long)]
+        next: bool,
+    },
+    /// Set the startup disk
+    Set {
+        /// Volume group name, volume group UUID or partition UUID
+        target: String,
+        /// Only start up from this disk on the next boot
+        #[arg(long)]
+        next: bool,
+    },
+    /// Give a startup disk a custom name or icon
+    Label {
+        /// Volume group name, label, volume group UUID or partition UUID
+        target: String,
+        /// Name to show instead of the volume group name
+        #[arg(long)]
+        name: Option<String>,
+        /// Icon name to show instead of the one for the detected system
+        #[arg(long)]
+        icon: Option<String>,
+        /// Go back to the volume group name and detected icon
+        #[arg(long, conflicts_with_all = ["name", "icon"])]
+        reset: bool,
+    },
+    /// Manage the NVRAM backups made before every change
+    #[command(subcommand)]
+    Backup(BackupCommand),
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// List the NVRAM backups, newest first
+    List,
+    /// Write a backup back to the NVRAM
+    Restore {
+        /// Backup name, as shown by `backup list`
+        name: String,
+    },
+}
+
+/// Returns the candidate name, if the volume group is known
+fn candidate_name(cand: &BootCandidate) -> Option<&str> {
+    if cand.volumes.is_empty() {
+        None
+    } else {
+        Some(get_vg_name(&cand.volumes))
+    }
+}
+
+/// Returns the name to show for a candidate, preferring the user's label
+fn display_name(cand: &BootCandidate, labels: &Labels) -> String {
+    labels
+        .get(cand.vg_uuid)
+        .and_then(|label| label.name.as_deref())
+        .or_else(|| candidate_name(cand))
+        .map_or_else(|| gettext("Unknown volume group"), str::to_string)
+}
+
+#[derive(Serialize)]
+struct VolumeJson<'a> {
+    name: &'a str,
+    is_system: bool,
+}
+
+/// A boot candidate in the `--json` output
+///
+/// The flags only appear in the output of the commands that set them.
+#[derive(Serialize)]
+struct CandidateJson<'a> {
+    name: Option<&'a str>,
+    label: Option<&'a str>,
+    icon: Option<&'a str>,
+    os: &'static str,
+    vg_uuid: String,
+    part_uuid: String,
+    volumes: Vec<VolumeJson<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<bool>,
+}
+
+impl<'a> CandidateJson<'a> {
+    fn new(cand: &'a BootCandidate, labels: &'a Labels) -> Self {
+        let label = labels.get(cand.vg_uuid);
+        CandidateJson {
+            name: candidate_name(cand),
+            label: label.and_then(|label| label.name.as_deref()),
+            icon: label.and_then(|label| label.icon.as_deref()),
+            os: OsType::classify(cand).id(),
+            vg_uuid: cand.vg_uuid.to_string(),
+            part_uuid: cand.part_uuid.to_string(),
+            volumes: cand
+                .volumes
+                .iter()
+                .map(|v| VolumeJson {
+                    name: &v.name,
+                    is_system: v.is_system,
+                })
+                .collect(),
+            default: None,
+            next: None,
+            current: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BackupJson<'a> {
+    name: &'a str,
+    timestamp: i64,
+    size: u64,
+    sha256: &'a str,
+}
+
+impl<'a> From<&'a Backup> for BackupJson<'a> {
+    fn from(backup: &'a Backup) -> Self {
+        BackupJson {
+            name: &backup.name,
+            timestamp: backup.timestamp,
+            size: backup.size,
+            sha256: &backup.checksum,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RestoredJson<'a> {
+    restored: &'a str,
+}
+
+/// Returns the `list --json` output
+fn list_json<'a>(
+    cands: &'a [BootCandidate],
+    default_cand: &BootCandidate,
+    current: Option<Uuid>,
+    labels: &'a Labels,
+) -> Vec<CandidateJson<'a>> {
+    cands
+        .iter()
+        .map(|cand| CandidateJson {
+            default: Some(is_same_candidate(cand, default_cand)),
+            current: Some(current == Some(cand.part_uuid)),
+            ..CandidateJson::new(cand, labels)
+        })
+        .collect()
+}
+
+/// Returns the `get --json` output
+fn get_json<'a>(
+    cand: &'a BootCandidate,
+    next: bool,
+    is_current: bool,
+    labels: &'a Labels,
+) -> CandidateJson<'a> {
+    CandidateJson {
+        next: Some(next),
+        current: Some(is_current),
+        ..CandidateJson::new(cand, labels)
+    }
+}
+
+fn print_json(value: &impl Serialize) {
+    // The output types only hold strings, numbers and booleans, which always serialize
+    println!("{}", serde_json::to_string(value).unwrap());
+}
+
+/// Finds the candidate matching a name, label, volume group UUID or partition UUID
+fn find_candidate(
+    cands: Vec<BootCandidate>,
+    labels: &Labels,
+    target: &str,
+) -> Result<BootCandidate> {
+    let uuid = Uuid::parse_str(target).ok();
+    let mut matches: Vec<BootCandidate> = cands
+        .into_iter()
+        .filter(|cand| match uuid {
+            Some(uuid) => cand.vg_uuid == uuid || cand.part_uuid == uuid,
+            None => {
+                candidate_name(cand) == Some(target)
+                    || labels
+                        .get(cand.vg_uuid)
+                        .is_some_and(|label| label.name.as_deref() == Some(target))
+            }
+        })
+        .collect();
+
+    match matches.len() {
+        0 => Err(Error::Bless(asahi_bless::Error::VolumeNotFound)),
+        1 => Ok(matches.remove(0)),
+        _ => Err(Error::Bless(asahi_bless::Error::Ambiguous)),
+    }
+}
+
+fn list(json: bool) -> Result<()> {
+    let startup_disk_library = startup_disk_library();
+
+    escalate_if_needed(startup_disk_library, Operation::GetBootVolume)?;
+    let default_cand = startup_disk_library.get_boot_volume(false)?;
+    escalate_if_needed(startup_disk_library, Operation::GetBootCandidates)?;
+    let cands = startup_disk_library.get_boot_candidates()?;
+    let current = startup_disk_library.current_part_uuid();
+    let is_current = |cand: &BootCandidate| current == Some(cand.part_uuid);
+    let labels = Labels::load()?;
+
+    if json {
+        print_json(&list_json(&cands, &default_cand, current, &labels));
+        return Ok(());
+    }
+
+    let width = cands
+        .iter()
+        .map(|cand| display_name(cand, &labels).chars().count())
+        .max()
+        .unwrap_or(0);
+    for cand in &cands {
+        let marker = if is_same_candidate(cand, &default_cand) {
+            '*'
+        } else {
+            ' '
+        };
+        println!(
+            "{} {:width$}  {}  {}{}",
+            marker,
+            display_name(cand, &labels),
+            cand.vg_uuid,
+            cand.part_uuid,
+            if is_current(cand) {
+                format!("  {}", gettext("(current)"))
+            } else {
+                String::new()
+            }
+        );
+    }
+    Ok(())
+}
+
+fn get(next: bool, json: bool) -> Result<()> {
+    let startup_disk_library = startup_disk_library();
+
+    escalate_if_needed(startup_disk_library, Operation::GetBootVolume)?;
+    let boot_volume = startup_disk_library.get_boot_volume(next)?;
+    escalate_if_needed(startup_disk_library, Operation::GetBootCandidates)?;
+    let cand = startup_disk_library
+        .get_boot_candidates()?
+        .into_iter()
+        .find(|cand| is_same_candidate(cand, &boot_volume))
+        .unwrap_or(boot_volume);
+
+    let is_current = startup_disk_library.current_part_uuid() == Some(cand.part_uuid);
+    let labels = Labels::load()?;
+
+    if json {
+        print_json(&get_json(&cand, next, is_current, &labels));
+    } else {
+        println!(
+            "{}  {}  {}",
+            display_name(&cand, &labels),
+            cand.vg_uuid,
+            cand.part_uuid
+        );
+    }
+    Ok(())
+}
+
+fn set(target: &str, next: bool, json: bool) -> Result<()> {
+    let startup_disk_library = startup_disk_library();
+
+    escalate_if_needed(startup_disk_library, Operation::GetBootCandidates)?;
+    let labels = Labels::load()?;
+    let cand = find_candidate(startup_disk_library.get_boot_candidates()?, &labels, target)?;
+    escalate_if_needed(startup_disk_library, Operation::SetBootVolume)?;
+    startup_disk_library.set_boot_volume(&cand, next)?;
+
+    if json {
+        print_json(&CandidateJson {
+            next: Some(next),
+            ..CandidateJson::new(&cand, &labels)
+        });
+    } else if next {
+        println!(
+            "{}",
+            gettext_f(
+                "Startup disk for the next boot set to {name}",
+                &[("name", &display_name(&cand, &labels))],
+            )
+        );
+    } else {
+        println!(
+            "{}",
+            gettext_f(
+                "Startup disk set to {name}",
+                &[("name", &display_name(&cand, &labels))],
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Changes the name and icon given, keeping the other one
+fn set_label(
+    target: &str,
+    name: Option<String>,
+    icon: Option<String>,
+    reset: bool,
+    json: bool,
+) -> Result<()> {
+    let startup_disk_library = startup_disk_library();
+
+    escalate_if_needed(startup_disk_library, Operation::GetBootCandidates)?;
+    let mut labels = Labels::load()?;
+    let cand = find_candidate(startup_disk_library.get_boot_candidates()?, &labels, target)?;
+    let label = if reset {
+        Label::default()
+    } else {
+        let old = labels.get(cand.vg_uuid).cloned().unwrap_or_default();
+        Label {
+            name: name.or(old.name),
+            icon: icon.or(old.icon),
+        }
+    };
+    labels.set(cand.vg_uuid, label);
+    labels.save()?;
+
+    if json {
+        print_json(&CandidateJson::new(&cand, &labels));
+    } else {
+        let old_name =
+            candidate_name(&cand).map_or_else(|| gettext("Unknown volume group"), str::to_string);
+        println!(
+            "{}",
+            gettext_f(
+                "{old_name} is now shown as {name}",
+                &[
+                    ("old_name", &old_name),
+                    ("name", &display_name(&cand, &labels))
+                ],
+            )
+        );
+    }
+    Ok(())
+}
+
+fn backup_list(json: bool) -> Result<()> {
+    let startup_disk_library = startup_disk_library();
+
+    escalate_if_needed(startup_disk_library, Operation::ListBackups)?;
+    let backups = startup_disk_library.list_backups()?;
+
+    if json {
+        let backups: Vec<BackupJson> = backups.iter().map(BackupJson::from).collect();
+        print_json(&backups);
+        return Ok(());
+    }
+
+    for backup in &backups {
+        let time = glib::DateTime::from_unix_local(backup.timestamp)
+            .and_then(|time| time.format("%x %X"))
+            .map_or_else(|_| backup.timestamp.to_string(), |time| time.to_string());
+        let size = ngettext_f(
+            "{n} byte",
+            "{n} bytes",
+            u32::try_from(backup.size).unwrap_or(u32::MAX),
+            &[],
+        );
+        println!("{}  {}  {}", backup.name, time, size);
+    }
+    Ok(())
+}
+
+fn backup_restore(name: &str, json: bool) -> Result<()> {
+    let startup_disk_library = startup_disk_library();
+
+    escalate_if_needed(startup_disk_library, Operation::RestoreBackup)?;
+    startup_disk_library.restore_backup(name)?;
+
+    if json {
+        print_json(&RestoredJson { restored: name });
+    } else {
+        println!(
+            "{}",
+            gettext_f("Restored NVRAM backup {name}", &[("name", name)])
+        );
+    }
+    Ok(())
+}
+
+/// Runs a command-line subcommand
+pub fn run(command: Command, json: bool) -> glib::ExitCode {
+    let result = match command {
+        Command::List => list(json),
+        Command::Get { next } => get(next, json),
+        Command::Set { target, next } => set(&target, next, json),
+        Command::Label {
+            target,
+            name,
+            icon,
+            reset,
+        } => set_label(&target, name, icon, reset, json),
+        Command::Backup(BackupCommand::List) => backup_list(json),
+        Command::Backup(BackupCommand::Restore { name }) => backup_restore(&name, json),
+    };
+
+    match result {
+        Ok(()) => glib::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("startup-disk: {e}");
+            glib::ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use startup_disk::Volume;
+
+    fn candidate(vg: u128, part: u128, name: &str) -> BootCandidate {
+        BootCandidate {
+            vg_uuid: Uuid::from_u128(vg),
+            part_uuid: Uuid::from_u128(part),
+            volumes: vec![
+                Volume {
+                    name: name.to_string(),
+                    is_system: true,
+                },
+                Volume {
+                    name: format!("{name} - Data"),
+                    is_system: false,
+                },
+            ],
+        }
+    }
+
+    fn candidates() -> Vec<BootCandidate> {
+        vec![
+            candidate(1, 2, "Macintosh HD"),
+            candidate(3, 4, "Asahi Linux"),
+        ]
+    }
+
+    fn labels() -> Labels {
+        let mut labels = Labels::default();
+        labels.set(
+            Uuid::from_u128(3),
+            Label {
+                name: Some("Work".to_string()),
+                icon: None,
+            },
+        );
+        labels
+    }
+
+    fn find(target: &str) -> Result<BootCandidate> {
+        find_candidate(candidates(), &labels(), target)
+    }
+
+    #[test]
+    fn finds_by_name() {
+        assert_eq!(find("Macintosh HD").unwrap().vg_uuid, Uuid::from_u128(1));
+    }
+
+    #[test]
+    fn finds_by_label() {
+        assert_eq!(find("Work").unwrap().vg_uuid, Uuid::from_u128(3));
+    }
+
+    #[test]
+    fn finds_by_uuid() {
+        let vg_uuid = Uuid::from_u128(3).to_string();
+        assert_eq!(find(&vg_uuid).unwrap().vg_uuid, Uuid::from_u128(3));
+        let part_uuid = Uuid::from_u128(2).to_string();
+        assert_eq!(find(&part_uuid).unwrap().vg_uuid, Uuid::from_u128(1));
+    }
+
+    #[test]
+    fn missing_match() {
+        assert!(matches!(
+            find("Windows"),
+            Err(Error::Bless(asahi_bless::Error::VolumeNotFound))
+        ));
+        // Volume names other than the system volume's do not match
+        assert!(find("Macintosh HD - Data").is_err());
+    }
+
+    #[test]
+    fn ambiguous_match() {
+        let mut cands = candidates();
+        cands.push(candidate(5, 6, "Macintosh HD"));
+        assert!(matches!(
+            find_candidate(cands, &labels(), "Macintosh HD"),
+            Err(Error::Bless(asahi_bless::Error::Ambiguous))
+        ));
+    }
+
+    #[test]
+    fn list_json_shape() {
+        let cands = candidates();
+        let labels = labels();
+        let value = serde_json::to_value(list_json(
+            &cands,
+            &cands[1],
+            Some(Uuid::from_u128(2)),
+            &labels,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            value,
+            json!([
+                {
+                    "name": "Macintosh HD",
+                    "label": null,
+                    "icon": null,
+                    "os": "macos",
+                    "vg_uuid": "00000000-0000-0000-0000-000000000001",
+                    "part_uuid": "00000000-0000-0000-0000-000000000002",
+                    "volumes": [
+                        { "name": "Macintosh HD", "is_system": true },
+                        { "name": "Macintosh HD - Data", "is_system": false },
+                    ],
+                    "default": false,
+                    "current": true,
+                },
+                {
+                    "name": "Asahi Linux",
+                    "label": "Work",
+                    "icon": null,
+                    "os": "asahi-linux",
+                    "vg_uuid": "00000000-0000-0000-0000-000000000003",
+                    "part_uuid": "00000000-0000-0000-0000-000000000004",
+                    "volumes": [
+                        { "name": "Asahi Linux", "is_system": true },
+                        { "name": "Asahi Linux - Data", "is_system": false },
+                    ],
+                    "default": true,
+                    "current": false,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn get_json_shape() {
+        let cands = candidates();
+        let labels = labels();
+        let value = serde_json::to_value(get_json(&cands[0], true, false, &labels)).unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "name": "Macintosh HD",
+                "label": null,
+                "icon": null,
+                "os": "macos",
+                "vg_uuid": "00000000-0000-0000-0000-000000000001",
+                "part_uuid": "00000000-0000-0000-0000-000000000002",
+                "volumes": [
+                    { "name": "Macintosh HD", "is_system": true },
+                    { "name": "Macintosh HD - Data", "is_system": false },
+                ],
+                "next": true,
+                "current": false,
+            })
+        );
+    }
+}
@@ -3,3 +3,9 @@
 pub static APP_ID: &str = "org.startup_disk.StartupDisk";
 pub static APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub static RESOURCE_BASE: &str = "/org/startup-disk/StartupDisk";
+pub static GETTEXT_PACKAGE: &str = "startup-disk";
+/// Where translations are installed, set by `make` from its `DATADIR`
+pub static LOCALEDIR: &str = match option_env!("LOCALEDIR") {
+    Some(dir) => dir,
+    None => "/usr/share/locale",
+};
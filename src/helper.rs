@@ -0,0 +1,380 @@
+// SPDX-License-Identifier: MIT
+
+use adw::gio::{
+    self, BusNameOwnerFlags, BusType, DBusCallFlags, DBusConnection, DBusMethodInvocation,
+    DBusNodeInfo,
+};
+use adw::glib::{self, clone, prelude::*, Variant};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use startup_disk::dbus::{
+    backup_to_tuple, candidate_from_tuple, candidate_to_tuple, error_to_dbus, BackupTuple,
+    CandidateTuple, BUS_NAME, ERROR_PREFIX, INTERFACE_NAME, INTERFACE_XML, OBJECT_PATH,
+};
+use startup_disk::{is_same_candidate, local_library, Operation};
+
+/// The helper exits after being idle for this long; D-Bus activation restarts it on demand
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// State shared by the method calls of a running helper
+struct Helper {
+    session_bus: bool,
+    /// When the helper last handled a call
+    last_activity: Cell<Instant>,
+    /// Authorization checks waiting for polkit, which may be showing a prompt
+    pending: Cell<u32>,
+}
+
+impl Helper {
+    fn is_idle(&self) -> bool {
+        self.pending.get() == 0 && self.last_activity.get().elapsed() >= IDLE_TIMEOUT
+    }
+
+    /// Finds out whether `sender` may perform `operation`, passing the answer to `done`
+    fn is_authorized(
+        self: &Rc<Self>,
+        connection: &DBusConnection,
+        sender: Option<&str>,
+        operation: Operation,
+        allow_interaction: bool,
+        done: impl FnOnce(Result<bool, glib::Error>) + 'static,
+    ) {
+        // The session bus is only used for testing, so there is nothing to protect
+        if self.session_bus {
+            return done(Ok(true));
+        }
+        let Some(sender) = sender else {
+            return done(Ok(false));
+        };
+
+        self.pending.set(self.pending.get() + 1);
+        let helper = self.clone();
+        check_authorization(
+            connection,
+            sender,
+            action_id(operation),
+            allow_interaction,
+            move |result| {
+                helper.pending.set(helper.pending.get() - 1);
+                helper.last_activity.set(Instant::now());
+                done(result);
+            },
+        );
+    }
+}
+
+/// Asks polkit whether `sender` is allowed to perform `action_id`, passing the
+/// answer to `done`
+///
+/// Without `allow_interaction`, this only succeeds if no authentication is
+/// needed, for example because an earlier authorization is still retained.
+/// The call does not block, so other clients are served while a prompt is open.
+fn check_authorization(
+    connection: &DBusConnection,
+    sender: &str,
+    action_id: &str,
+    allow_interaction: bool,
+    done: impl FnOnce(Result<bool, glib::Error>) + 'static,
+) {
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name".to_string(), sender.to_variant())]),
+    );
+    let details: HashMap<String, String> = HashMap::new();
+    // Flag 1 is AllowUserInteraction
+    let flags = u32::from(allow_interaction);
+    let parameters = (subject, action_id, details, flags, "").to_variant();
+
+    connection.call(
+        Some("org.freedesktop.PolicyKit1"),
+        "/org/freedesktop/PolicyKit1/Authority",
+        "org.freedesktop.PolicyKit1.Authority",
+        "CheckAuthorization",
+        Some(&parameters),
+        Some(&<((bool, bool, HashMap<String, String>),)>::static_variant_type()),
+        DBusCallFlags::NONE,
+        i32::MAX,
+        gio::Cancellable::NONE,
+        move |reply| {
+            done(reply.map(|reply| {
+                let ((is_authorized, _, _),) = reply
+                    .get::<((bool, bool, HashMap<String, String>),)>()
+                    .unwrap();
+                is_authorized
+            }))
+        },
+    );
+}
+
+/// A D-Bus method that reads or changes the NVRAM or the disk
+#[derive(Clone, Copy)]
+enum Method {
+    /// Returns the NVRAM device node, which is as sensitive as the boot volume
+    GetNvramDevice,
+    Perform(Operation),
+}
+
+impl Method {
+    fn from_name(method: &str) -> Option<Method> {
+        match method {
+            "GetBootCandidates" => Some(Method::Perform(Operation::GetBootCandidates)),
+            "GetNvramDevice" => Some(Method::GetNvramDevice),
+            "GetBootVolume" => Some(Method::Perform(Operation::GetBootVolume)),
+            "SetBootVolume" => Some(Method::Perform(Operation::SetBootVolume)),
+            "ListBackups" => Some(Method::Perform(Operation::ListBackups)),
+            "RestoreBackup" => Some(Method::Perform(Operation::RestoreBackup)),
+            _ => None,
+        }
+    }
+
+    /// The operation the sender must be authorized for
+    fn operation(self) -> Operation {
+        match self {
+            Method::GetNvramDevice => Operation::GetBootVolume,
+            Method::Perform(operation) => operation,
+        }
+    }
+}
+
+fn action_id(operation: Operation) -> &'static str {
+    match operation {
+        Operation::GetBootCandidates => "org.startup_disk.StartupDisk.get-boot-candidates",
+        Operation::GetBootVolume => "org.startup_disk.StartupDisk.get-boot-volume",
+        Operation::SetBootVolume => "org.startup_disk.StartupDisk.set-boot-volume",
+        Operation::ListBackups => "org.startup_disk.StartupDisk.list-backups",
+        Operation::RestoreBackup => "org.startup_disk.StartupDisk.restore-backup",
+    }
+}
+
+fn return_not_authorized(invocation: DBusMethodInvocation, message: &str) {
+    invocation.return_dbus_error(&format!("{ERROR_PREFIX}.NotAuthorized"), message);
+}
+
+fn return_invalid_args(invocation: DBusMethodInvocation, message: &str) {
+    invocation.return_dbus_error("org.freedesktop.DBus.Error.InvalidArgs", message);
+}
+
+fn handle_method_call(
+    helper: &Rc<Helper>,
+    connection: &DBusConnection,
+    sender: Option<&str>,
+    method: &str,
+    parameters: Variant,
+    invocation: DBusMethodInvocation,
+) {
+    // Lets clients find out whether they are authorized, or get authorized up
+    // front, without performing the operation itself
+    if method == "GetAccess" || method == "Authorize" {
+        let Some(operation) = parameters
+            .get::<(String,)>()
+            .and_then(|(name,)| Operation::from_name(&name))
+        else {
+            return return_invalid_args(invocation, "Expected a valid operation name");
+        };
+        let get_access = method == "GetAccess";
+        helper.is_authorized(
+            connection,
+            sender,
+            operation,
+            !get_access,
+            move |result| match (get_access, result) {
+                (true, Ok(authorized)) => {
+                    invocation.return_value(Some(&(authorized,).to_variant()))
+                }
+                (true, Err(_)) => invocation.return_value(Some(&(false,).to_variant())),
+                (false, Ok(true)) => invocation.return_value(Some(&().to_variant())),
+                (false, Ok(false)) => return_not_authorized(
+                    invocation,
+                    &format!("Not authorized to perform {}", action_id(operation)),
+                ),
+                (false, Err(e)) => return_not_authorized(
+                    invocation,
+                    &format!("Failed to check authorization: {e}"),
+                ),
+            },
+        );
+        return;
+    }
+
+    let Some(method) = Method::from_name(method) else {
+        invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("Unknown method {method}"),
+        );
+        return;
+    };
+
+    let operation = method.operation();
+    helper.is_authorized(
+        connection,
+        sender,
+        operation,
+        true,
+        move |result| match result {
+            Ok(true) => perform_method_call(method, parameters, invocation),
+            Ok(false) => return_not_authorized(
+                invocation,
+                &format!("Not authorized to perform {}", action_id(operation)),
+            ),
+            Err(e) => {
+                return_not_authorized(invocation, &format!("Failed to check authorization: {e}"))
+            }
+        },
+    );
+}
+
+/// Performs a method call the sender is authorized for
+fn perform_method_call(method: Method, parameters: Variant, invocation: DBusMethodInvocation) {
+    let startup_disk_library = local_library();
+    let result = match method {
+        Method::GetNvramDevice => startup_disk_library
+            .nvram_device()
+            .map(|device| (device,).to_variant()),
+        Method::Perform(Operation::GetBootCandidates) => {
+            startup_disk_library.get_boot_candidates().map(|cands| {
+                let cands: Vec<CandidateTuple> = cands.iter().map(candidate_to_tuple).collect();
+                (cands,).to_variant()
+            })
+        }
+        Method::Perform(Operation::GetBootVolume) => {
+            let Some((next,)) = parameters.get::<(bool,)>() else {
+                return return_invalid_args(invocation, "Expected (b)");
+            };
+            startup_disk_library
+                .get_boot_volume(next)
+                .map(|cand| (candidate_to_tuple(&cand),).to_variant())
+        }
+        Method::Perform(Operation::SetBootVolume) => {
+            let Some((cand, next)) = parameters.get::<(CandidateTuple, bool)>() else {
+                return return_invalid_args(invocation, "Expected ((ssa(sb))b)");
+            };
+            let Some(cand) = candidate_from_tuple(cand) else {
+                return return_invalid_args(invocation, "Invalid boot candidate");
+            };
+            // Only write candidates found on this machine, whatever the client sent
+            match startup_disk_library.get_boot_candidates() {
+                Ok(cands) => {
+                    let Some(known) = cands.iter().find(|known| is_same_candidate(known, &cand))
+                    else {
+                        return return_invalid_args(invocation, "Not a boot candidate");
+                    };
+                    startup_disk_library
+                        .set_boot_volume(known, next)
+                        .map(|_| ().to_variant())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Method::Perform(Operation::ListBackups) => {
+            startup_disk_library.list_backups().map(|backups| {
+                let backups: Vec<BackupTuple> = backups.iter().map(backup_to_tuple).collect();
+                (backups,).to_variant()
+            })
+        }
+        Method::Perform(Operation::RestoreBackup) => {
+            let Some((name,)) = parameters.get::<(String,)>() else {
+                return return_invalid_args(invocation, "Expected (s)");
+            };
+            startup_disk_library
+                .restore_backup(&name)
+                .map(|_| ().to_variant())
+        }
+    };
+
+    match result {
+        Ok(value) => invocation.return_value(Some(&value)),
+        Err(e) => {
+            let (name, message) = error_to_dbus(&e);
+            invocation.return_dbus_error(&name, &message);
+        }
+    }
+}
+
+/// Runs the privileged helper service until it goes idle or loses its bus name
+pub fn run(session_bus: bool) -> glib::ExitCode {
+    let node_info = DBusNodeInfo::for_xml(INTERFACE_XML).expect("Failed to parse D-Bus interface.");
+    let main_loop = glib::MainLoop::new(None, false);
+    let helper = Rc::new(Helper {
+        session_bus,
+        last_activity: Cell::new(Instant::now()),
+        pending: Cell::new(0),
+    });
+    let name_lost = Rc::new(Cell::new(false));
+
+    let bus_type = if session_bus {
+        BusType::Session
+    } else {
+        BusType::System
+    };
+    let owner_id = gio::bus_own_name(
+        bus_type,
+        BUS_NAME,
+        BusNameOwnerFlags::NONE,
+        clone!(
+            #[strong]
+            helper,
+            move |connection, _| {
+                let interface_info = node_info
+                    .lookup_interface(INTERFACE_NAME)
+                    .expect("Failed to find D-Bus interface.");
+                let helper = helper.clone();
+                connection
+                    .register_object(OBJECT_PATH, &interface_info)
+                    .method_call(
+                        move |connection, sender, _, _, method, parameters, invocation| {
+                            handle_method_call(
+                                &helper,
+                                &connection,
+                                sender,
+                                method,
+                                parameters,
+                                invocation,
+                            );
+                            helper.last_activity.set(Instant::now());
+                        },
+                    )
+                    .build()
+                    .expect("Failed to register D-Bus object.");
+            }
+        ),
+        |_, _| {},
+        clone!(
+            #[strong]
+            main_loop,
+            #[strong]
+            name_lost,
+            move |_, name| {
+                eprintln!("Lost D-Bus name {name}");
+                name_lost.set(true);
+                main_loop.quit();
+            }
+        ),
+    );
+
+    glib::timeout_add_seconds_local(
+        10,
+        clone!(
+            #[strong]
+            main_loop,
+            move || {
+                if helper.is_idle() {
+                    main_loop.quit();
+                    return glib::ControlFlow::Break;
+                }
+                glib::ControlFlow::Continue
+            }
+        ),
+    );
+
+    main_loop.run();
+    gio::bus_unown_name(owner_id);
+
+    if name_lost.get() {
+        glib::ExitCode::FAILURE
+    } else {
+        glib::ExitCode::SUCCESS
+    }
+}
@@ -2,16 +2,102 @@
 
 mod application;
 mod boot_candidate;
+mod cli;
 mod config;
-mod startup_disk;
+mod helper;
+mod preferences;
+mod reboot;
+mod settings;
 mod window;
 
 use adw::prelude::*;
+use clap::Parser;
+use gettextrs::{
+    bind_textdomain_codeset, bindtextdomain, gettext, setlocale, textdomain, LocaleCategory,
+};
 use gtk::{gio, glib};
+use startup_disk::gettext_f;
+use std::env;
+use std::path::PathBuf;
 
 use application::StartupDiskApplication;
 
+/// Choose the startup volume on Apple Silicon systems
+///
+/// Without a subcommand, the graphical interface is started.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Print machine-readable JSON output
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Run the privileged D-Bus helper service
+    #[arg(long, hide = true)]
+    helper: bool,
+
+    /// Run the helper on the session bus, for testing
+    #[arg(long, hide = true, requires = "helper")]
+    session: bool,
+
+    /// Use an NVRAM image file instead of the NVRAM device, for testing
+    #[arg(long, global = true, value_name = "FILE")]
+    nvram_image: Option<PathBuf>,
+
+    /// NVRAM device node, instead of the discovered one
+    #[arg(
+        long,
+        global = true,
+        value_name = "DEVICE",
+        conflicts_with = "nvram_image"
+    )]
+    nvram_device: Option<String>,
+
+    /// Directory of boot candidate fixtures used with --nvram-image
+    #[arg(long, global = true, value_name = "DIR", requires = "nvram_image")]
+    fixtures: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+}
+
+/// Sets up translations for the GUI, the command line and the library's messages
+fn setup_gettext() {
+    setlocale(LocaleCategory::LcAll, "");
+    bindtextdomain(config::GETTEXT_PACKAGE, config::LOCALEDIR)
+        .expect("Unable to bind the text domain");
+    bind_textdomain_codeset(config::GETTEXT_PACKAGE, "UTF-8")
+        .expect("Unable to set the text domain encoding");
+    textdomain(config::GETTEXT_PACKAGE).expect("Unable to switch to the text domain");
+}
+
 fn main() -> glib::ExitCode {
+    setup_gettext();
+    let args = Args::parse();
+
+    // The preference comes after the environment, and the helper only trusts
+    // the system-wide configuration
+    let use_preference = !args.helper && env::var_os("STARTUP_DISK_NVRAM_DEVICE").is_none();
+    if let Some(device) = args
+        .nvram_device
+        .or_else(|| use_preference.then(settings::nvram_device).flatten())
+    {
+        startup_disk::use_nvram_device(device);
+    }
+    if let Some(image) = args.nvram_image {
+        startup_disk::use_nvram_image(image, args.fixtures);
+    }
+
+    // Run as the privileged D-Bus helper when requested
+    if args.helper {
+        return helper::run(args.session);
+    }
+
+    // Run a command-line subcommand when one is given
+    if let Some(command) = args.command {
+        return cli::run(command, args.json);
+    }
+
     // Register and include resources
     gio::resources_register_include!("startup-disk.gresource")
         .expect("Failed to register resources.");
@@ -19,6 +105,25 @@ fn main() -> glib::ExitCode {
     // Create a new application
     let app = StartupDiskApplication::new();
 
-    // Run the application
-    app.run()
+    // Only one instance may run at a time, as concurrent writers can corrupt the
+    // NVRAM; launching a second one just raises the existing window
+    if let Err(e) = app.register(gio::Cancellable::NONE) {
+        eprintln!(
+            "{}",
+            gettext_f(
+                "Failed to register application: {error}",
+                &[("error", &e.to_string())],
+            )
+        );
+        return glib::ExitCode::FAILURE;
+    }
+    if app.is_remote() {
+        eprintln!(
+            "{}",
+            gettext("Startup Disk is already running, activating the existing window")
+        );
+    }
+
+    // Run the application; arguments were already handled above
+    app.run_with_args::<&str>(&[])
 }
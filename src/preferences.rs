@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: MIT
+
+mod imp {
+    use adw::glib::{self, subclass::InitializingObject};
+    use adw::gtk::CompositeTemplate;
+    use adw::subclass::prelude::*;
+    use adw::{EntryRow, PreferencesDialog, SwitchRow};
+
+    #[derive(Default, CompositeTemplate)]
+    #[template(resource = "/org/startup-disk/StartupDisk/preferences.ui")]
+    pub struct StartupDiskPreferences {
+        #[template_child]
+        pub confirm_before_change: TemplateChild<SwitchRow>,
+        #[template_child]
+        pub offer_restart: TemplateChild<SwitchRow>,
+        #[template_child]
+        pub next_boot_only: TemplateChild<SwitchRow>,
+        #[template_child]
+        pub show_recovery: TemplateChild<SwitchRow>,
+        #[template_child]
+        pub nvram_device: TemplateChild<EntryRow>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for StartupDiskPreferences {
+        const NAME: &'static str = "StartupDiskPreferences";
+        type Type = super::StartupDiskPreferences;
+        type ParentType = PreferencesDialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for StartupDiskPreferences {}
+    impl WidgetImpl for StartupDiskPreferences {}
+    impl AdwDialogImpl for StartupDiskPreferences {}
+    impl PreferencesDialogImpl for StartupDiskPreferences {}
+}
+
+use adw::gio;
+use adw::glib::{self, subclass::types::ObjectSubclassIsExt};
+use adw::gtk::{Accessible, Buildable, ConstraintTarget, ShortcutManager, Widget};
+use adw::prelude::*;
+use adw::{Dialog, PreferencesDialog};
+
+glib::wrapper! {
+    pub struct StartupDiskPreferences(ObjectSubclass<imp::StartupDiskPreferences>)
+        @extends PreferencesDialog, Dialog, Widget,
+        @implements Accessible, Buildable, ConstraintTarget, ShortcutManager;
+}
+
+impl StartupDiskPreferences {
+    /// Creates the dialog with its rows bound to `settings`, which writes changes
+    /// back immediately
+    pub fn new(settings: &gio::Settings) -> Self {
+        let dialog: Self = glib::Object::new();
+
+        let imp = dialog.imp();
+        settings
+            .bind(
+                "confirm-before-change",
+                &*imp.confirm_before_change,
+                "active",
+            )
+            .build();
+        settings
+            .bind("offer-restart", &*imp.offer_restart, "active")
+            .build();
+        settings
+            .bind("next-boot-only", &*imp.next_boot_only, "active")
+            .build();
+        settings
+            .bind("show-recovery", &*imp.show_recovery, "active")
+            .build();
+        settings
+            .bind("nvram-device", &*imp.nvram_device, "text")
+            .build();
+        // Only direct NVRAM access honors the preference
+        imp.nvram_device
+            .set_sensitive(!startup_disk::uses_dbus_helper());
+
+        dialog
+    }
+}
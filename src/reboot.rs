@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: MIT
+
+use adw::gio::{self, BusType, DBusCallFlags};
+use adw::glib::{self, prelude::*};
+use std::env;
+
+pub trait RebootTrait {
+    /// Asks for a reboot, passing the outcome to `done`
+    ///
+    /// This returns right away, as the request may wait for a polkit prompt.
+    fn reboot(&self, done: Box<dyn FnOnce(Result<(), glib::Error>)>);
+}
+
+/// Reboots through systemd-logind's `org.freedesktop.login1.Manager` interface
+pub struct Logind {
+    bus_type: BusType,
+}
+
+impl RebootTrait for Logind {
+    fn reboot(&self, done: Box<dyn FnOnce(Result<(), glib::Error>)>) {
+        gio::bus_get(self.bus_type, gio::Cancellable::NONE, move |connection| {
+            let connection = match connection {
+                Ok(connection) => connection,
+                Err(e) => return done(Err(e)),
+            };
+
+            // Allow plenty of time for the user to answer the polkit prompt
+            connection.call(
+                Some("org.freedesktop.login1"),
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+                "Reboot",
+                Some(&(true,).to_variant()),
+                None,
+                DBusCallFlags::ALLOW_INTERACTIVE_AUTHORIZATION,
+                i32::MAX,
+                gio::Cancellable::NONE,
+                move |result| done(result.map(|_| ())),
+            );
+        });
+    }
+}
+
+/// Returns the reboot implementation
+///
+/// Setting `USE_LOGIND_BUS=session` talks to a stand-in logind service on the
+/// session bus instead, for testing.
+pub fn reboot_manager() -> Box<dyn RebootTrait> {
+    let bus_type = match env::var("USE_LOGIND_BUS").as_deref() {
+        Ok("session") => BusType::Session,
+        _ => BusType::System,
+    };
+
+    Box::new(Logind { bus_type })
+}
@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: MIT
+
+use adw::gio::{self, prelude::*};
+use adw::glib;
+
+use crate::config;
+
+/// Returns the application settings
+///
+/// This is `None` when the schema is not installed, such as when running from
+/// the source tree, in which case the defaults apply.
+pub fn settings() -> Option<gio::Settings> {
+    gio::SettingsSchemaSource::default()?
+        .lookup(config::APP_ID, true)
+        .map(|_| gio::Settings::new(config::APP_ID))
+}
+
+/// Returns the NVRAM device set in the preferences, if any
+pub fn nvram_device() -> Option<String> {
+    settings()
+        .map(|settings| settings.string("nvram-device").to_string())
+        .filter(|device| !device.is_empty())
+}
+
+/// Size and maximized state of the main window, remembered between runs
+#[derive(Debug, PartialEq, Eq)]
+pub struct WindowState {
+    /// Unmaximized width, or -1 for the natural width
+    pub width: i32,
+    /// Unmaximized height, or -1 for the natural height
+    pub height: i32,
+    pub is_maximized: bool,
+}
+
+impl WindowState {
+    pub fn load(settings: &gio::Settings) -> Self {
+        WindowState {
+            width: settings.int("window-width"),
+            height: settings.int("window-height"),
+            is_maximized: settings.boolean("is-maximized"),
+        }
+    }
+
+    pub fn save(&self, settings: &gio::Settings) -> Result<(), glib::BoolError> {
+        settings.set_int("window-width", self.width)?;
+        settings.set_int("window-height", self.height)?;
+        settings.set_boolean("is-maximized", self.is_maximized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process::Command;
+
+    /// Returns settings from the schema in the source tree, kept in memory
+    fn memory_settings() -> gio::Settings {
+        let dir = env::temp_dir().join(format!("startup-disk-schemas-{}", std::process::id()));
+        let status = Command::new("glib-compile-schemas")
+            .arg("--strict")
+            .arg("--targetdir")
+            .arg(&dir)
+            .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/res"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let source = gio::SettingsSchemaSource::from_directory(&dir, None, false).unwrap();
+        let schema = source.lookup(config::APP_ID, false).unwrap();
+        let backend = gio::memory_settings_backend_new();
+        gio::Settings::new_full(&schema, Some(&backend), None)
+    }
+
+    #[test]
+    fn window_state_round_trip() {
+        let settings = memory_settings();
+        assert_eq!(
+            WindowState::load(&settings),
+            WindowState {
+                width: -1,
+                height: -1,
+                is_maximized: false,
+            }
+        );
+
+        let state = WindowState {
+            width: 800,
+            height: 600,
+            is_maximized: true,
+        };
+        state.save(&settings).unwrap();
+        assert_eq!(WindowState::load(&settings), state);
+
+        // The view switcher writes through to the settings, and a new
+        // window's switcher starts from the stored view
+        settings
+            .create_action("view-mode")
+            .change_state(&"list".to_variant());
+        assert_eq!(settings.string("view-mode"), "list");
+        let restored = settings.create_action("view-mode").state().unwrap();
+        assert_eq!(restored.str(), Some("list"));
+    }
+}
@@ -1,15 +1,15 @@
 // SPDX-License-Identifier: MIT
 
 mod imp {
-    use adw::gio::ListStore;
+    use adw::gio::{self, FileMonitor, ListStore};
     use adw::glib::{self, subclass::InitializingObject};
     use adw::prelude::*;
     use adw::subclass::prelude::*;
     use adw::{
-        gtk::{GridView, Stack},
-        ApplicationWindow,
+        gtk::{Button, GridView, ListView, Stack},
+        ApplicationWindow, StatusPage,
     };
-    use std::cell::RefCell;
+    use std::cell::{Cell, RefCell};
 
     #[derive(gtk::CompositeTemplate, glib::Properties, Default)]
     #[template(resource = "/org/startup-disk/StartupDisk/window.ui")]
@@ -19,8 +19,30 @@ mod imp {
         pub stack: TemplateChild<Stack>,
         #[template_child]
         pub grid_view: TemplateChild<GridView>,
+        #[template_child]
+        pub list_view: TemplateChild<ListView>,
+        #[template_child]
+        pub view_stack: TemplateChild<Stack>,
+        #[template_child]
+        pub error_page: TemplateChild<StatusPage>,
+        #[template_child]
+        pub lock_button: TemplateChild<Button>,
 
         pub boot_candidates: RefCell<Option<ListStore>>,
+        /// Pending read of the boot candidates, dropped if the window goes away
+        pub load_task: RefCell<Option<glib::JoinHandle<()>>>,
+        /// Whether a write to the NVRAM is in progress
+        pub busy: Cell<bool>,
+        /// Whether changing the startup disk or restoring a backup needs to be
+        /// unlocked first
+        pub locked: Cell<bool>,
+        /// Watches for partitions appearing and disappearing
+        pub device_monitor: RefCell<Option<FileMonitor>>,
+        /// Rescan waiting for a burst of device changes to settle
+        pub rescan_source: RefCell<Option<glib::SourceId>>,
+        /// Application settings, if the schema is installed
+        #[property(get, construct_only)]
+        pub settings: RefCell<Option<gio::Settings>>,
 
         #[property(get, set)]
         supported: RefCell<bool>,
@@ -48,16 +70,30 @@ mod imp {
             // Setup grid
             self.obj().setup_list_store();
             self.obj().setup_factory();
+            self.obj().setup_actions();
+            self.obj().setup_device_monitor();
+            self.obj().setup_settings();
+            self.obj().setup_view_mode();
+            self.obj().restore_window_state();
 
             // Add signal for supported property
             self.obj().connect_notify(Some("supported"), |window, _| {
                 if window.supported() {
-                    window.add_boot_candidates();
-                    window.imp().stack.set_visible_child_name("boot_candidates");
+                    window.load_boot_candidates();
                 }
             });
         }
 
+        fn dispose(&self) {
+            // The worker thread cannot be interrupted, but its result is no longer wanted
+            if let Some(task) = self.load_task.take() {
+                task.abort();
+            }
+            if let Some(source) = self.rescan_source.take() {
+                source.remove();
+            }
+        }
+
         fn properties() -> &'static [glib::ParamSpec] {
             Self::derived_properties()
         }
@@ -72,25 +108,89 @@ mod imp {
     }
 
     impl WidgetImpl for StartupDiskWindow {}
-    impl WindowImpl for StartupDiskWindow {}
+    impl WindowImpl for StartupDiskWindow {
+        fn close_request(&self) -> glib::Propagation {
+            self.obj().save_window_state();
+            self.parent_close_request()
+        }
+    }
     impl ApplicationWindowImpl for StartupDiskWindow {}
     impl AdwApplicationWindowImpl for StartupDiskWindow {}
 }
 
-use adw::gio::{ActionGroup, ActionMap, ListStore};
-use adw::glib::{self, subclass::types::ObjectSubclassIsExt};
+use adw::gio::{
+    self, ActionEntry, ActionGroup, ActionMap, FileMonitorEvent, FileMonitorFlags, ListStore,
+    SimpleAction,
+};
+use adw::glib::{self, subclass::types::ObjectSubclassIsExt, SourceId, VariantTy};
 use adw::prelude::*;
 use adw::{
     gtk::{
-        Accessible, Buildable, ConstraintTarget, ListItem, Native, Root, ShortcutManager, Widget,
-        Window,
+        Accessible, Align, Box as GtkBox, Buildable, Button, ConstraintTarget, ListBox, ListItem,
+        Native, Orientation, Root, SelectionMode, ShortcutManager, SignalListItemFactory,
+        SingleSelection, ToggleButton, Widget, Window,
     },
-    Application, ApplicationWindow,
+    ActionRow, AlertDialog, Application, ApplicationWindow, EntryRow, ResponseAppearance,
 };
+use gettextrs::gettext;
+use startup_disk::BootCandidate;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+use uuid::Uuid;
 
 use crate::boot_candidate::object::BootCandidateObject;
+use crate::boot_candidate::row::BootCandidateRow;
 use crate::boot_candidate::BootCandidateWidget;
-use crate::startup_disk::startup_disk_library;
+use crate::reboot::reboot_manager;
+use crate::settings::WindowState;
+use startup_disk::{
+    escalate_if_needed, get_vg_name, gettext_f, is_same_candidate, ngettext_f,
+    startup_disk_library, Access, Backup, Error, Label, Labels, Operation, OsType, Result,
+    StartupDiskTrait, ICONS,
+};
+
+/// Seconds to wait before restarting, giving the user a chance to cancel
+const RESTART_COUNTDOWN: u32 = 10;
+
+/// udev links a node here for every partition on every disk
+static BY_PARTUUID_DIR: &str = "/dev/disk/by-partuuid";
+
+/// Operations that write the NVRAM, which are authorized by unlocking
+const WRITE_OPERATIONS: [Operation; 2] = [Operation::SetBootVolume, Operation::RestoreBackup];
+
+/// Milliseconds to wait for device changes to settle before rescanning
+const RESCAN_DELAY_MS: u64 = 500;
+
+/// Everything read from the backend to fill the grid
+struct BootCandidates {
+    default: BootCandidate,
+    next: BootCandidate,
+    /// Partition UUID of the running system
+    current: Option<Uuid>,
+    candidates: Vec<BootCandidate>,
+    labels: Labels,
+}
+
+/// Reads the boot candidates and the default and next boot volumes
+///
+/// This blocks on the NVRAM and the disk, so it runs on a worker thread.
+fn read_boot_candidates(
+    startup_disk_library: &'static dyn StartupDiskTrait,
+) -> Result<BootCandidates> {
+    escalate_if_needed(startup_disk_library, Operation::GetBootVolume)?;
+    escalate_if_needed(startup_disk_library, Operation::GetBootCandidates)?;
+
+    Ok(BootCandidates {
+        default: startup_disk_library.get_boot_volume(false)?,
+        // Falls back to the default if no override is pending
+        next: startup_disk_library.get_boot_volume(true)?,
+        current: startup_disk_library.current_part_uuid(),
+        candidates: startup_disk_library.get_boot_candidates()?,
+        // A broken labels file should not hide the startup disks
+        labels: Labels::load().unwrap_or_default(),
+    })
+}
 
 glib::wrapper! {
     pub struct StartupDiskWindow(ObjectSubclass<imp::StartupDiskWindow>)
@@ -99,10 +199,17 @@ glib::wrapper! {
 }
 
 impl StartupDiskWindow {
-    pub fn new<A: IsA<Application>>(application: &A, supported: bool) -> Self {
+    /// Creates the window, which keeps its preferences and state in `settings`
+    /// if given
+    pub fn new<A: IsA<Application>>(
+        application: &A,
+        supported: bool,
+        settings: Option<&gio::Settings>,
+    ) -> Self {
         glib::Object::builder()
             .property("application", application)
             .property("supported", supported)
+            .property("settings", settings)
             .build()
     }
 
@@ -111,107 +218,1026 @@ impl StartupDiskWindow {
         self.imp().boot_candidates.borrow().clone().unwrap()
     }
 
-    /// Creates the list store and sets up a single selection model
+    /// Creates the list store and sets up a single selection model, shared by
+    /// the grid and list views
     fn setup_list_store(&self) {
         let list_store = ListStore::new::<BootCandidateObject>();
         self.imp().boot_candidates.replace(Some(list_store));
 
-        let selection_model = adw::gtk::SingleSelection::new(Some(self.get_list_store()));
-        selection_model.connect_selection_changed(|selection, _, _| {
-            if let Some(object) = selection
-                .selected_item()
-                .and_downcast::<BootCandidateObject>()
-            {
-                let startup_disk_library = startup_disk_library();
-                if startup_disk_library.needs_escalation("set_boot_volume") {
-                    sudo::escalate_if_needed().unwrap();
-                }
-                startup_disk_library
-                    .set_boot_volume(
-                        "/dev/mtd/by-name/nvram",
-                        object.imp().boot_candidate.borrow().as_ref().unwrap(),
-                        false,
-                    )
-                    .unwrap();
-            }
-        });
+        // Selecting a candidate only marks it as pending; nothing is written until
+        // the apply action is confirmed
+        let selection_model = SingleSelection::new(Some(self.get_list_store()));
+        selection_model.set_autoselect(false);
+        selection_model.connect_selection_changed(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |_, _, _| window.update_actions()
+        ));
         self.imp().grid_view.set_model(Some(&selection_model));
+        self.imp().list_view.set_model(Some(&selection_model));
     }
 
-    /// Creates the factory which creates, binds, and unbinds boot candidate widgets
-    fn setup_factory(&self) {
-        let factory = adw::gtk::SignalListItemFactory::new();
+    fn selection_model(&self) -> SingleSelection {
+        self.imp()
+            .grid_view
+            .model()
+            .and_downcast::<SingleSelection>()
+            .unwrap()
+    }
 
-        // Creates widgets
-        factory.connect_setup(|_, list_item| {
-            let widget = BootCandidateWidget::new();
-            list_item.set_property("child", Some(&widget));
-        });
+    /// Returns the currently selected boot candidate, if any
+    fn selected_boot_candidate(&self) -> Option<BootCandidateObject> {
+        self.selection_model()
+            .selected_item()
+            .and_downcast::<BootCandidateObject>()
+    }
 
-        // Binds widget properties to object properties
-        factory.connect_bind(|_, list_item| {
-            let list_item = list_item.downcast_ref::<ListItem>().unwrap();
+    fn set_action_enabled(&self, name: &str, enabled: bool) {
+        self.lookup_action(name)
+            .and_downcast::<SimpleAction>()
+            .unwrap()
+            .set_enabled(enabled);
+    }
 
-            let object = list_item
-                .item()
-                .and_downcast::<BootCandidateObject>()
-                .unwrap();
-            let widget = list_item
-                .child()
-                .and_downcast::<BootCandidateWidget>()
-                .unwrap();
+    /// Enables the actions that write the NVRAM only while unlocked and idle,
+    /// and the apply action only when a different candidate is pending
+    fn update_actions(&self) {
+        let idle = !self.imp().busy.get();
+        let can_write = idle && !self.imp().locked.get();
+        let pending = self
+            .selected_boot_candidate()
+            .is_some_and(|object| !object.is_default());
 
-            widget.bind(&object);
-        });
+        self.set_action_enabled("apply", can_write && pending);
+        self.set_action_enabled("start-up-once", can_write);
+        self.set_action_enabled("backups", idle);
+    }
 
-        // Unbinds widget properties from object properties
-        factory.connect_unbind(|_, list_item| {
-            let list_item = list_item.downcast_ref::<ListItem>().unwrap();
-            let widget = list_item
-                .child()
-                .and_downcast::<BootCandidateWidget>()
-                .unwrap();
-            widget.unbind();
-        });
+    /// Blocks further changes while the NVRAM is being written
+    fn set_busy(&self, busy: bool) {
+        self.imp().busy.set(busy);
+        self.imp().grid_view.set_sensitive(!busy);
+        self.imp().list_view.set_sensitive(!busy);
+        self.update_actions();
+    }
 
-        self.imp().grid_view.set_factory(Some(&factory));
+    /// Checks whether the NVRAM can be written without authorization, showing
+    /// the unlock button if not
+    fn update_access(&self) {
+        let startup_disk_library = startup_disk_library();
+        self.spawn_backend_call(
+            move || {
+                Ok(WRITE_OPERATIONS
+                    .into_iter()
+                    .all(|operation| startup_disk_library.access(operation) == Access::Granted))
+            },
+            |window, granted| {
+                let locked = granted.is_ok_and(|granted| !granted);
+                window.imp().locked.set(locked);
+                window.imp().lock_button.set_visible(locked);
+                window.update_actions();
+            },
+        );
     }
 
-    fn add_boot_candidates(&self) {
+    /// Asks for authorization to change the startup disk and restore backups
+    fn unlock(&self) {
         let startup_disk_library = startup_disk_library();
+        self.imp().lock_button.set_sensitive(false);
+        self.spawn_backend_call(
+            move || {
+                WRITE_OPERATIONS
+                    .into_iter()
+                    .try_for_each(|operation| escalate_if_needed(startup_disk_library, operation))
+            },
+            |window, result| {
+                window.imp().lock_button.set_sensitive(true);
+                match result {
+                    Ok(()) => window.update_access(),
+                    Err(e) => window.show_error(&gettext("Unable to Unlock"), &e.to_string()),
+                }
+            },
+        );
+    }
 
-        // Get default boot candidate
-        if startup_disk_library.needs_escalation("get_boot_volume") {
-            sudo::escalate_if_needed().unwrap();
+    /// Runs a blocking backend call on a worker thread
+    ///
+    /// `done` is called with the result on the main loop. The window is only
+    /// weakly referenced meanwhile, so the result is dropped if it is closed.
+    /// A panic in `call` is passed to `done` as an error.
+    fn spawn_backend_call<T, F, D>(&self, call: F, done: D) -> glib::JoinHandle<()>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+        D: FnOnce(&Self, Result<T>) + 'static,
+    {
+        let handle = gio::spawn_blocking(call);
+        let window = self.downgrade();
+        glib::spawn_future_local(async move {
+            let result = handle.await.unwrap_or_else(|e| Err(panic_error(e)));
+            if let Some(window) = window.upgrade() {
+                done(&window, result);
+            }
+        })
+    }
+
+    fn show_error(&self, heading: &str, error: &str) {
+        let dialog = AlertDialog::new(Some(heading), Some(error));
+        dialog.add_response("close", &gettext("_Close"));
+        dialog.present(Some(self));
+    }
+
+    /// Installs the window actions
+    fn setup_actions(&self) {
+        // Used by the retry button on the error page
+        let reload_action = ActionEntry::builder("reload")
+            .activate(move |window: &Self, _, _| window.load_boot_candidates())
+            .build();
+
+        // Writes the pending selection after confirmation
+        let apply_action = ActionEntry::builder("apply")
+            .activate(move |window: &Self, _, _| {
+                if let Some(object) = window.selected_boot_candidate() {
+                    let next = window.boolean_setting("next-boot-only", false);
+                    window.confirm_set_boot_volume(&object, next);
+                }
+            })
+            .build();
+
+        let start_up_once_action = ActionEntry::builder("start-up-once")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |window: &Self, _, parameter| {
+                let vg_uuid = parameter.and_then(|p| p.get::<String>()).unwrap();
+                if let Some(object) = window.find_boot_candidate(&vg_uuid) {
+                    window.confirm_set_boot_volume(&object, true);
+                }
+            })
+            .build();
+        let edit_label_action = ActionEntry::builder("edit-label")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |window: &Self, _, parameter| {
+                let vg_uuid = parameter.and_then(|p| p.get::<String>()).unwrap();
+                if let Some(object) = window.find_boot_candidate(&vg_uuid) {
+                    window.edit_label(&object);
+                }
+            })
+            .build();
+        let restart_action = ActionEntry::builder("restart")
+            .activate(move |window: &Self, _, _| window.restart_with_countdown())
+            .build();
+        let backups_action = ActionEntry::builder("backups")
+            .activate(move |window: &Self, _, _| window.show_backups())
+            .build();
+        let unlock_action = ActionEntry::builder("unlock")
+            .activate(move |window: &Self, _, _| window.unlock())
+            .build();
+        self.add_action_entries([
+            reload_action,
+            apply_action,
+            start_up_once_action,
+            edit_label_action,
+            restart_action,
+            backups_action,
+            unlock_action,
+        ]);
+        self.update_actions();
+    }
+
+    /// Asks for confirmation before writing the boot volume, unless turned off
+    fn confirm_set_boot_volume(&self, object: &BootCandidateObject, next: bool) {
+        if !self.boolean_setting("confirm-before-change", true) {
+            self.set_boot_volume(object, next);
+            return;
         }
-        let default_cand = startup_disk_library
-            .get_boot_volume("/dev/mtd/by-name/nvram", false)
-            .unwrap();
 
-        // Add boot candidates to list store
-        if startup_disk_library.needs_escalation("get_boot_candidates") {
-            sudo::escalate_if_needed().unwrap();
+        let name = object.name();
+        let (heading, body, response_label) = if next {
+            (
+                gettext("Start Up Once?"),
+                gettext_f(
+                    "The computer will start up from “{name}” the next time it restarts, then return to the current startup disk.",
+                    &[("name", &name)],
+                ),
+                gettext("_Start Up Once"),
+            )
+        } else {
+            (
+                gettext("Change Startup Disk?"),
+                gettext_f(
+                    "The computer will start up from “{name}” from now on.",
+                    &[("name", &name)],
+                ),
+                gettext("_Set as Startup Disk"),
+            )
+        };
+
+        let dialog = AlertDialog::new(Some(&heading), Some(&body));
+        dialog.add_responses(&[("cancel", &gettext("_Cancel")), ("set", &response_label)]);
+        dialog.set_response_appearance("set", ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("set"));
+        dialog.set_close_response("cancel");
+
+        dialog.choose(
+            self,
+            None::<&adw::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[strong]
+                object,
+                move |response| {
+                    if response == "set" {
+                        window.set_boot_volume(&object, next);
+                    }
+                }
+            ),
+        );
+    }
+
+    /// Lets the user rename a boot candidate and choose its icon
+    fn edit_label(&self, object: &BootCandidateObject) {
+        let cand = object.boot_candidate();
+        let label = match Labels::load() {
+            Ok(labels) => labels.get(cand.vg_uuid).cloned().unwrap_or_default(),
+            Err(e) => {
+                self.show_error(&gettext("Unable to Edit Label"), &e.to_string());
+                return;
+            }
+        };
+
+        let name_row = EntryRow::builder()
+            .title(gettext("Name"))
+            .text(label.name.as_deref().unwrap_or_default())
+            .build();
+        let list_box = ListBox::builder()
+            .selection_mode(SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .build();
+        list_box.append(&name_row);
+
+        // The first button goes back to the icon of the detected system
+        let icon_box = GtkBox::builder()
+            .spacing(6)
+            .halign(Align::Center)
+            .margin_top(12)
+            .build();
+        let automatic = ToggleButton::builder()
+            .label(gettext("Automatic"))
+            .active(label.icon.is_none())
+            .build();
+        icon_box.append(&automatic);
+        let icon_buttons: Vec<(&str, ToggleButton)> = ICONS
+            .iter()
+            .map(|&icon| {
+                let button = ToggleButton::builder()
+                    .icon_name(icon)
+                    .group(&automatic)
+                    .active(label.icon.as_deref() == Some(icon))
+                    .build();
+                icon_box.append(&button);
+                (icon, button)
+            })
+            .collect();
+
+        let content = GtkBox::new(Orientation::Vertical, 0);
+        content.append(&list_box);
+        content.append(&icon_box);
+
+        let dialog = AlertDialog::new(
+            Some(&gettext("Edit Label")),
+            Some(&gettext_f(
+                "Leave the name empty to show “{name}”.",
+                &[("name", get_vg_name(&cand.volumes))],
+            )),
+        );
+        dialog.set_extra_child(Some(&content));
+        dialog.add_responses(&[("cancel", &gettext("_Cancel")), ("save", &gettext("_Save"))]);
+        dialog.set_response_appearance("save", ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+
+        dialog.choose(
+            self,
+            None::<&adw::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[strong]
+                object,
+                move |response| {
+                    if response != "save" {
+                        return;
+                    }
+                    let name = name_row.text().trim().to_string();
+                    let label = Label {
+                        name: (!name.is_empty()).then_some(name),
+                        icon: icon_buttons
+                            .iter()
+                            .find(|(_, button)| button.is_active())
+                            .map(|(icon, _)| icon.to_string()),
+                    };
+                    window.save_label(&object, label);
+                }
+            ),
+        );
+    }
+
+    fn save_label(&self, object: &BootCandidateObject, label: Label) {
+        let cand = object.boot_candidate();
+        let result = Labels::load().and_then(|mut labels| {
+            labels.set(cand.vg_uuid, label.clone());
+            labels.save()
+        });
+
+        match result {
+            Ok(()) => object.update(cand, Some(&label)),
+            Err(e) => self.show_error(&gettext("Unable to Save Label"), &e.to_string()),
         }
+    }
 
-        for (idx, cand) in startup_disk_library
-            .get_boot_candidates()
-            .unwrap()
-            .into_iter()
-            .enumerate()
+    /// Looks up a boot candidate object by volume group UUID
+    fn find_boot_candidate(&self, vg_uuid: &str) -> Option<BootCandidateObject> {
+        self.get_list_store()
+            .iter::<BootCandidateObject>()
+            .flatten()
+            .find(|object| object.vg_uuid() == vg_uuid)
+    }
+
+    /// Writes the boot volume in the background
+    fn set_boot_volume(&self, object: &BootCandidateObject, next: bool) {
+        let startup_disk_library = startup_disk_library();
+
+        // Keep the application running until the write is done, even if the
+        // window is closed meanwhile
+        let hold = self.application().map(|app| app.hold());
+        let cand = object.boot_candidate();
+        self.set_busy(true);
+        self.spawn_backend_call(
+            move || startup_disk_library.set_boot_volume(&cand, next),
+            glib::clone!(
+                #[strong]
+                object,
+                move |window, result| {
+                    drop(hold);
+                    window.set_busy(false);
+                    match result {
+                        Ok(()) => window.boot_volume_set(&object, next),
+                        Err(e) => window.show_set_boot_volume_error(&object, next, &e),
+                    }
+                }
+            ),
+        );
+    }
+
+    /// Updates the default and next boot markers after a successful write
+    fn boot_volume_set(&self, object: &BootCandidateObject, next: bool) {
+        for other in self
+            .get_list_store()
+            .iter::<BootCandidateObject>()
+            .flatten()
         {
-            let is_default =
-                cand.part_uuid == default_cand.part_uuid && cand.vg_uuid == default_cand.vg_uuid;
-
-            let object = BootCandidateObject::new(cand);
-            self.get_list_store().append(&object);
-
-            if is_default {
-                self.imp()
-                    .grid_view
-                    .model()
-                    .unwrap()
-                    .select_item(idx as u32, true);
+            if next {
+                other.set_is_next(other == *object && !other.is_default());
+            } else {
+                other.set_is_default(other == *object);
+                if other == *object {
+                    other.set_is_next(false);
+                }
+            }
+        }
+        self.update_actions();
+
+        if self.boolean_setting("offer-restart", true) {
+            self.offer_restart(object);
+        }
+    }
+
+    /// Offers to restart into the startup disk that was just set
+    fn offer_restart(&self, object: &BootCandidateObject) {
+        let dialog = AlertDialog::new(
+            Some(&gettext("Startup Disk Changed")),
+            Some(&gettext_f(
+                "Restart now to start up from “{name}”?",
+                &[("name", &object.name())],
+            )),
+        );
+        dialog.add_responses(&[
+            ("later", &gettext("_Not Now")),
+            ("restart", &gettext("_Restart…")),
+        ]);
+        dialog.set_response_appearance("restart", ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("restart"));
+        dialog.set_close_response("later");
+
+        dialog.choose(
+            self,
+            None::<&adw::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |response| {
+                    if response == "restart" {
+                        window.restart_with_countdown();
+                    }
+                }
+            ),
+        );
+    }
+
+    /// Restarts the computer after a short countdown, which can be cancelled
+    fn restart_with_countdown(&self) {
+        let remaining = Rc::new(Cell::new(RESTART_COUNTDOWN));
+        let countdown_body = |seconds: u32| {
+            ngettext_f(
+                "The computer will restart in {n} second.",
+                "The computer will restart in {n} seconds.",
+                seconds,
+                &[],
+            )
+        };
+
+        let dialog = AlertDialog::new(
+            Some(&gettext("Restart")),
+            Some(&countdown_body(remaining.get())),
+        );
+        dialog.add_responses(&[
+            ("cancel", &gettext("_Cancel")),
+            ("restart", &gettext("_Restart Now")),
+        ]);
+        dialog.set_response_appearance("restart", ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let countdown: Rc<RefCell<Option<SourceId>>> = Rc::default();
+        let source_id = glib::timeout_add_seconds_local(
+            1,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[weak]
+                dialog,
+                #[strong]
+                countdown,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    remaining.set(remaining.get() - 1);
+                    if remaining.get() > 0 {
+                        dialog.set_body(&countdown_body(remaining.get()));
+                        return glib::ControlFlow::Continue;
+                    }
+
+                    // The source is removed by returning Break
+                    countdown.take();
+                    dialog.force_close();
+                    window.reboot();
+                    glib::ControlFlow::Break
+                }
+            ),
+        );
+        countdown.replace(Some(source_id));
+
+        dialog.choose(
+            self,
+            None::<&adw::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |response| {
+                    if let Some(source_id) = countdown.take() {
+                        source_id.remove();
+                        if response == "restart" {
+                            window.reboot();
+                        }
+                    }
+                }
+            ),
+        );
+    }
+
+    fn reboot(&self) {
+        // Outside the macro, where xgettext finds it
+        let title = gettext("Unable to Restart");
+        reboot_manager().reboot(Box::new(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |result| {
+                if let Err(e) = result {
+                    window.show_error(&title, e.message());
+                }
+            }
+        )));
+    }
+
+    /// Reports a failed write and offers to retry it
+    fn show_set_boot_volume_error(&self, object: &BootCandidateObject, next: bool, error: &Error) {
+        let dialog = AlertDialog::new(
+            Some(&gettext("Unable to Change Startup Disk")),
+            Some(&gettext_f(
+                "Could not set {name} as the startup disk.\n\n{error}",
+                &[("name", &object.name()), ("error", &error.to_string())],
+            )),
+        );
+        dialog.add_responses(&[("close", &gettext("_Close")), ("retry", &gettext("_Retry"))]);
+        dialog.set_response_appearance("retry", ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("retry"));
+        dialog.set_close_response("close");
+
+        dialog.choose(
+            self,
+            None::<&adw::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[strong]
+                object,
+                move |response| {
+                    if response == "retry" {
+                        window.set_boot_volume(&object, next);
+                    }
+                }
+            ),
+        );
+    }
+
+    /// Lists the NVRAM backups and offers to restore one
+    fn show_backups(&self) {
+        let startup_disk_library = startup_disk_library();
+        self.spawn_backend_call(
+            move || {
+                escalate_if_needed(startup_disk_library, Operation::ListBackups)?;
+                startup_disk_library.list_backups()
+            },
+            |window, result| match result {
+                Ok(backups) => window.show_backups_dialog(backups),
+                Err(e) => window.show_error(&gettext("Unable to List Backups"), &e.to_string()),
+            },
+        );
+    }
+
+    fn show_backups_dialog(&self, backups: Vec<Backup>) {
+        let body = if backups.is_empty() {
+            gettext("No backups have been made yet. The NVRAM is backed up every time the startup disk is changed.")
+        } else {
+            gettext("The NVRAM is backed up every time the startup disk is changed.")
+        };
+        let dialog = AlertDialog::new(Some(&gettext("NVRAM Backups")), Some(&body));
+        dialog.add_response("close", &gettext("_Close"));
+
+        if !backups.is_empty() {
+            let list_box = ListBox::new();
+            list_box.set_selection_mode(SelectionMode::None);
+            list_box.add_css_class("boxed-list");
+
+            for backup in backups {
+                let row = ActionRow::new();
+                row.set_title(&backup_time(&backup));
+                row.set_subtitle(&backup.name);
+
+                let button = Button::with_mnemonic(&gettext("_Restore…"));
+                button.set_valign(Align::Center);
+                // Restoring is authorized up front with the unlock button
+                if self.imp().locked.get() {
+                    button.set_sensitive(false);
+                    button.set_tooltip_text(Some(&gettext("Unlock to Restore Backups")));
+                }
+                button.connect_clicked(glib::clone!(
+                    #[weak(rename_to = window)]
+                    self,
+                    #[weak]
+                    dialog,
+                    move |_| {
+                        dialog.close();
+                        window.confirm_restore_backup(&backup);
+                    }
+                ));
+                row.add_suffix(&button);
+                list_box.append(&row);
             }
+            dialog.set_extra_child(Some(&list_box));
+        }
+
+        dialog.present(Some(self));
+    }
+
+    /// Asks for confirmation before writing a backup back to the NVRAM
+    fn confirm_restore_backup(&self, backup: &Backup) {
+        let dialog = AlertDialog::new(
+            Some(&gettext("Restore Backup?")),
+            Some(&gettext_f(
+                "The NVRAM will be restored to its state from {time}. The current contents are backed up first.",
+                &[("time", &backup_time(backup))],
+            )),
+        );
+        dialog.add_responses(&[
+            ("cancel", &gettext("_Cancel")),
+            ("restore", &gettext("_Restore")),
+        ]);
+        dialog.set_response_appearance("restore", ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let name = backup.name.clone();
+        dialog.choose(
+            self,
+            None::<&adw::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |response| {
+                    if response == "restore" {
+                        window.restore_backup(&name);
+                    }
+                }
+            ),
+        );
+    }
+
+    fn restore_backup(&self, name: &str) {
+        let startup_disk_library = startup_disk_library();
+        let hold = self.application().map(|app| app.hold());
+        let name = name.to_string();
+        self.set_busy(true);
+        self.spawn_backend_call(
+            move || startup_disk_library.restore_backup(&name),
+            move |window, result| {
+                drop(hold);
+                window.set_busy(false);
+                match result {
+                    // The restored NVRAM may point at a different startup disk
+                    Ok(()) => window.load_boot_candidates(),
+                    Err(e) => {
+                        window.show_error(&gettext("Unable to Restore Backup"), &e.to_string())
+                    }
+                }
+            },
+        );
+    }
+
+    /// Sets up the factories for the grid and list views
+    fn setup_factory(&self) {
+        self.imp().grid_view.set_factory(Some(&list_item_factory(
+            BootCandidateWidget::new,
+            BootCandidateWidget::bind,
+            BootCandidateWidget::unbind,
+        )));
+        self.imp().list_view.set_factory(Some(&list_item_factory(
+            BootCandidateRow::new,
+            BootCandidateRow::bind,
+            BootCandidateRow::unbind,
+        )));
+    }
+
+    /// Installs the action switching between the grid and list views
+    ///
+    /// The choice is remembered when the settings schema is installed.
+    fn setup_view_mode(&self) {
+        let action = match self.imp().settings.borrow().as_ref() {
+            Some(settings) => settings.create_action("view-mode"),
+            None => SimpleAction::new_stateful(
+                "view-mode",
+                Some(VariantTy::STRING),
+                &"grid".to_variant(),
+            )
+            .upcast(),
+        };
+        action.connect_state_notify(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |action| window.show_view_mode(action)
+        ));
+        self.show_view_mode(&action);
+        self.add_action(&action);
+    }
+
+    fn show_view_mode(&self, action: &impl IsA<gio::Action>) {
+        let mode = action
+            .state()
+            .and_then(|state| state.get::<String>())
+            .unwrap_or_default();
+        let page = if mode == "list" { "list" } else { "grid" };
+        self.imp().view_stack.set_visible_child_name(page);
+    }
+
+    /// Fills the grid in the background, showing the loading page meanwhile
+    fn load_boot_candidates(&self) {
+        if let Some(task) = self.imp().load_task.take() {
+            task.abort();
+        }
+        self.get_list_store().remove_all();
+        self.update_actions();
+
+        let startup_disk_library = startup_disk_library();
+        self.imp().stack.set_visible_child_name("loading");
+        let task = self.spawn_backend_call(
+            move || read_boot_candidates(startup_disk_library),
+            |window, result| {
+                window.imp().load_task.take();
+                match result {
+                    Ok(boot_candidates) => {
+                        window.update_boot_candidates(boot_candidates);
+                        window.imp().stack.set_visible_child_name("boot_candidates");
+                        window.update_access();
+                    }
+                    Err(e) => window.show_error_page(&e),
+                }
+                window.update_actions();
+            },
+        );
+        self.imp().load_task.replace(Some(task));
+    }
+
+    /// Re-reads the boot candidates and updates the grid in place, keeping the
+    /// pending selection
+    ///
+    /// Falls back to a full load when the grid is not showing, such as after an
+    /// error. Writes update the grid themselves, so nothing is done meanwhile.
+    pub fn refresh(&self) {
+        if !self.supported() || self.imp().busy.get() {
+            return;
+        }
+        if self.imp().stack.visible_child_name().as_deref() != Some("boot_candidates") {
+            self.load_boot_candidates();
+            return;
+        }
+        if let Some(task) = self.imp().load_task.take() {
+            task.abort();
+        }
+
+        let startup_disk_library = startup_disk_library();
+        let task = self.spawn_backend_call(
+            move || read_boot_candidates(startup_disk_library),
+            |window, result| {
+                window.imp().load_task.take();
+                match result {
+                    Ok(boot_candidates) => window.update_boot_candidates(boot_candidates),
+                    Err(e) => window.show_error_page(&e),
+                }
+                window.update_actions();
+            },
+        );
+        self.imp().load_task.replace(Some(task));
+    }
+
+    /// Updates the grid when the preferences change which candidates are shown
+    fn setup_settings(&self) {
+        let Some(settings) = self.settings() else {
+            return;
+        };
+
+        settings.connect_changed(
+            Some("show-recovery"),
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |_, _| window.refresh()
+            ),
+        );
+    }
+
+    /// Reads a boolean setting, or `default` if the schema is not installed
+    fn boolean_setting(&self, key: &str, default: bool) -> bool {
+        self.imp()
+            .settings
+            .borrow()
+            .as_ref()
+            .map_or(default, |settings| settings.boolean(key))
+    }
+
+    /// Restores the size and maximized state the window was last closed with
+    fn restore_window_state(&self) {
+        let Some(settings) = self.settings() else {
+            return;
+        };
+
+        let state = WindowState::load(&settings);
+        self.set_default_size(state.width, state.height);
+        if state.is_maximized {
+            self.maximize();
         }
     }
+
+    fn save_window_state(&self) {
+        let Some(settings) = self.settings() else {
+            return;
+        };
+
+        // The default size tracks the unmaximized size
+        let (width, height) = self.default_size();
+        let state = WindowState {
+            width,
+            height,
+            is_maximized: self.is_maximized(),
+        };
+        if let Err(e) = state.save(&settings) {
+            eprintln!("Failed to save the window state: {e}");
+        }
+    }
+
+    /// Rescans whenever a partition is added or removed
+    ///
+    /// Changes come in bursts while a disk is attached or partitioned, so the
+    /// rescan waits for them to settle.
+    fn setup_device_monitor(&self) {
+        // Not every system has the directory, and mock and image backends do
+        // not need it, so there is nothing to report if it cannot be watched
+        let Ok(monitor) = gio::File::for_path(BY_PARTUUID_DIR)
+            .monitor_directory(FileMonitorFlags::NONE, None::<&gio::Cancellable>)
+        else {
+            return;
+        };
+
+        monitor.connect_changed(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |_, _, _, event| {
+                if matches!(event, FileMonitorEvent::Created | FileMonitorEvent::Deleted) {
+                    window.queue_rescan();
+                }
+            }
+        ));
+        self.imp().device_monitor.replace(Some(monitor));
+    }
+
+    fn queue_rescan(&self) {
+        if let Some(source) = self.imp().rescan_source.take() {
+            source.remove();
+        }
+
+        let source = glib::timeout_add_local_once(
+            Duration::from_millis(RESCAN_DELAY_MS),
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move || {
+                    window.imp().rescan_source.take();
+                    window.refresh();
+                }
+            ),
+        );
+        self.imp().rescan_source.replace(Some(source));
+    }
+
+    fn show_error_page(&self, error: &Error) {
+        self.imp()
+            .error_page
+            .set_description(Some(&error.to_string()));
+        self.imp().stack.set_visible_child_name("error");
+    }
+
+    /// Brings the grid in line with the backend
+    ///
+    /// Existing objects are kept and updated, so the widgets bound to them and
+    /// the pending selection survive a rescan. If the selected candidate went
+    /// away, or nothing was selected yet, the default is selected.
+    fn update_boot_candidates(&self, boot_candidates: BootCandidates) {
+        let BootCandidates {
+            default,
+            next,
+            current,
+            mut candidates,
+            labels,
+        } = boot_candidates;
+        let list_store = self.get_list_store();
+
+        // The startup disk stays visible even if it is a recovery system
+        if !self.boolean_setting("show-recovery", true) {
+            candidates.retain(|cand| {
+                OsType::classify(cand) != OsType::Recovery || is_same_candidate(cand, &default)
+            });
+        }
+        let selected = self.selected_boot_candidate();
+
+        // Drop candidates that went away
+        let mut position = 0;
+        while position < list_store.n_items() {
+            let object = list_store
+                .item(position)
+                .and_downcast::<BootCandidateObject>()
+                .unwrap();
+            if candidates.iter().any(|cand| object.matches(cand)) {
+                position += 1;
+            } else {
+                list_store.remove(position);
+            }
+        }
+
+        // Add new candidates and move existing ones into the backend's order
+        for (idx, cand) in candidates.into_iter().enumerate() {
+            let idx = idx as u32;
+            let existing = (idx..list_store.n_items()).find_map(|position| {
+                list_store
+                    .item(position)
+                    .and_downcast::<BootCandidateObject>()
+                    .filter(|object| object.matches(&cand))
+                    .map(|object| (position, object))
+            });
+            let object = match existing {
+                Some((position, object)) => {
+                    if position != idx {
+                        list_store.remove(position);
+                        list_store.insert(idx, &object);
+                    }
+                    let label = labels.get(cand.vg_uuid);
+                    object.update(cand, label);
+                    object
+                }
+                None => {
+                    let label = labels.get(cand.vg_uuid);
+                    let object = BootCandidateObject::new(cand, label);
+                    list_store.insert(idx, &object);
+                    object
+                }
+            };
+
+            let is_default = object.matches(&default);
+            object.set_is_default(is_default);
+            object.set_is_next(!is_default && object.matches(&next));
+            object.set_is_current(
+                current.is_some_and(|part_uuid| object.part_uuid() == part_uuid.to_string()),
+            );
+        }
+
+        let objects: Vec<BootCandidateObject> = list_store.iter().flatten().collect();
+        let position = selected
+            .and_then(|selected| objects.iter().position(|object| *object == selected))
+            .or_else(|| objects.iter().position(|object| object.is_default()));
+        if let Some(position) = position {
+            self.selection_model().set_selected(position as u32);
+        }
+    }
+}
+
+/// Creates a factory which creates, binds, and unbinds boot candidate widgets
+fn list_item_factory<W: IsA<Widget>>(
+    create: fn() -> W,
+    bind: fn(&W, &BootCandidateObject),
+    unbind: fn(&W),
+) -> SignalListItemFactory {
+    let factory = SignalListItemFactory::new();
+
+    // Creates widgets
+    factory.connect_setup(move |_, list_item| {
+        let widget = create();
+        list_item.set_property("child", Some(&widget));
+    });
+
+    // Binds widget properties to object properties
+    factory.connect_bind(move |_, list_item| {
+        let list_item = list_item.downcast_ref::<ListItem>().unwrap();
+
+        let object = list_item
+            .item()
+            .and_downcast::<BootCandidateObject>()
+            .unwrap();
+        let widget = list_item.child().and_downcast::<W>().unwrap();
+
+        bind(&widget, &object);
+    });
+
+    // Unbinds widget properties from object properties
+    factory.connect_unbind(move |_, list_item| {
+        let list_item = list_item.downcast_ref::<ListItem>().unwrap();
+        let widget = list_item.child().and_downcast::<W>().unwrap();
+        unbind(&widget);
+    });
+
+    factory
+}
+
+/// Formats the creation time of a backup in the local time zone
+fn backup_time(backup: &Backup) -> String {
+    glib::DateTime::from_unix_local(backup.timestamp)
+        .and_then(|time| time.format("%x %X"))
+        .map_or_else(|_| backup.name.clone(), |time| time.to_string())
+}
+
+/// Turns the payload of a panicked backend call into an error
+fn panic_error(payload: Box<dyn std::any::Any + Send>) -> Error {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    Error::Internal(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_are_errors() {
+        let payload = std::panic::catch_unwind(|| panic!("index out of bounds")).unwrap_err();
+        match panic_error(payload) {
+            Error::Internal(message) => assert_eq!(message, "index out of bounds"),
+            error => panic!("unexpected error {error:?}"),
+        }
+
+        let uuid = Uuid::nil();
+        let payload = std::panic::catch_unwind(|| panic!("no volume group {uuid}")).unwrap_err();
+        assert!(
+            matches!(panic_error(payload), Error::Internal(m) if m.contains("no volume group"))
+        );
+    }
 }
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: MIT
+
+//! Reading and changing the startup disk of Apple Silicon Macs
+//!
+//! [`startup_disk_library`] picks the backend for the running system: the NVRAM
+//! directly when running as root, the privileged D-Bus helper otherwise, and
+//! the mock and NVRAM image backends for testing. Nothing here depends on GTK;
+//! the graphical interface, the command line interface and the helper service
+//! of the `startup-disk` binary are all built on this crate.
+
+mod startup_disk;
+
+pub use startup_disk::*;
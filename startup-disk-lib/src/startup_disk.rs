@@ -0,0 +1,461 @@
+// SPDX-License-Identifier: MIT
+
+mod asahi;
+mod backup;
+mod current;
+// Shared with the helper service of the startup-disk binary, not a stable API
+#[doc(hidden)]
+pub mod dbus;
+mod device;
+mod error;
+mod i18n;
+mod image;
+mod labels;
+mod lock;
+mod mock;
+mod os_type;
+
+pub use asahi_bless::{BootCandidate, Volume};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+pub use backup::Backup;
+pub use device::use_nvram_device;
+pub use error::Error;
+// Used by the startup-disk binary for its own messages
+#[doc(hidden)]
+pub use i18n::{gettext_f, ngettext_f};
+pub use labels::{Label, Labels, ICONS};
+use lock::lock_nvram;
+pub use os_type::OsType;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn is_asahi() -> bool {
+    Path::new("/proc/device-tree/chosen/asahi,system-fw-version").exists()
+}
+
+/// An operation a backend can perform
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    GetBootCandidates,
+    GetBootVolume,
+    SetBootVolume,
+    ListBackups,
+    RestoreBackup,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] = [
+        Operation::GetBootCandidates,
+        Operation::GetBootVolume,
+        Operation::SetBootVolume,
+        Operation::ListBackups,
+        Operation::RestoreBackup,
+    ];
+
+    /// Returns the name used for the operation on D-Bus
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::GetBootCandidates => "get_boot_candidates",
+            Operation::GetBootVolume => "get_boot_volume",
+            Operation::SetBootVolume => "set_boot_volume",
+            Operation::ListBackups => "list_backups",
+            Operation::RestoreBackup => "restore_backup",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Operation> {
+        Operation::ALL.into_iter().find(|op| op.name() == name)
+    }
+}
+
+/// Whether this process may perform an operation right now
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// The operation can be performed as is
+    Granted,
+    /// The process has to re-run itself as root first
+    NeedsRoot,
+    /// The helper service has to be authorized through polkit first
+    NeedsAuthorization,
+}
+
+/// A backend; calls may block, so they can be made from worker threads
+pub trait StartupDiskTrait: Sync {
+    fn is_supported(&self) -> bool;
+    fn access(&self, operation: Operation) -> Access;
+    /// Obtains access to `operation`, prompting the user if needed
+    fn authorize(&self, operation: Operation) -> Result<()>;
+    fn nvram_device(&self) -> Result<String>;
+    /// Returns the partition UUID of the running system, if it is a boot candidate
+    fn current_part_uuid(&self) -> Option<Uuid>;
+    fn get_boot_candidates(&self) -> Result<Vec<BootCandidate>>;
+    fn get_boot_volume(&self, next: bool) -> Result<BootCandidate>;
+    fn set_boot_volume(&self, cand: &BootCandidate, next: bool) -> Result<()>;
+    fn list_backups(&self) -> Result<Vec<Backup>>;
+    fn restore_backup(&self, name: &str) -> Result<()>;
+}
+
+enum StartupDiskLibrary {
+    AsahiBless(asahi::AsahiBlessLibrary),
+    DBus(dbus::DBusLibrary),
+    Image(image::ImageLibrary),
+    Mock(mock::MockLibrary),
+}
+
+impl StartupDiskTrait for StartupDiskLibrary {
+    fn is_supported(&self) -> bool {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.is_supported(),
+            StartupDiskLibrary::DBus(lib) => lib.is_supported(),
+            StartupDiskLibrary::Image(lib) => lib.is_supported(),
+            StartupDiskLibrary::Mock(lib) => lib.is_supported(),
+        }
+    }
+
+    fn access(&self, operation: Operation) -> Access {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.access(operation),
+            StartupDiskLibrary::DBus(lib) => lib.access(operation),
+            StartupDiskLibrary::Image(lib) => lib.access(operation),
+            StartupDiskLibrary::Mock(lib) => lib.access(operation),
+        }
+    }
+
+    fn authorize(&self, operation: Operation) -> Result<()> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.authorize(operation),
+            StartupDiskLibrary::DBus(lib) => lib.authorize(operation),
+            StartupDiskLibrary::Image(lib) => lib.authorize(operation),
+            StartupDiskLibrary::Mock(lib) => lib.authorize(operation),
+        }
+    }
+
+    fn nvram_device(&self) -> Result<String> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.nvram_device(),
+            StartupDiskLibrary::DBus(lib) => lib.nvram_device(),
+            StartupDiskLibrary::Image(lib) => lib.nvram_device(),
+            StartupDiskLibrary::Mock(lib) => lib.nvram_device(),
+        }
+    }
+
+    fn current_part_uuid(&self) -> Option<Uuid> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.current_part_uuid(),
+            StartupDiskLibrary::DBus(lib) => lib.current_part_uuid(),
+            StartupDiskLibrary::Image(lib) => lib.current_part_uuid(),
+            StartupDiskLibrary::Mock(lib) => lib.current_part_uuid(),
+        }
+    }
+
+    fn get_boot_candidates(&self) -> Result<Vec<BootCandidate>> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.get_boot_candidates(),
+            StartupDiskLibrary::DBus(lib) => lib.get_boot_candidates(),
+            StartupDiskLibrary::Image(lib) => lib.get_boot_candidates(),
+            StartupDiskLibrary::Mock(lib) => lib.get_boot_candidates(),
+        }
+    }
+    fn get_boot_volume(&self, next: bool) -> Result<BootCandidate> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.get_boot_volume(next),
+            StartupDiskLibrary::DBus(lib) => lib.get_boot_volume(next),
+            StartupDiskLibrary::Image(lib) => lib.get_boot_volume(next),
+            StartupDiskLibrary::Mock(lib) => lib.get_boot_volume(next),
+        }
+    }
+    fn set_boot_volume(&self, cand: &BootCandidate, next: bool) -> Result<()> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => {
+                set_and_verify(lib, Some(&lib.nvram_device()?), cand, next)
+            }
+            // The helper already locks and verifies the write on its side
+            StartupDiskLibrary::DBus(lib) => lib.set_boot_volume(cand, next),
+            StartupDiskLibrary::Image(lib) => {
+                set_and_verify(lib, Some(&lib.nvram_device()?), cand, next)
+            }
+            // There is no device node to lock, but concurrent writers are still detected
+            StartupDiskLibrary::Mock(lib) => set_and_verify(lib, None, cand, next),
+        }
+    }
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.list_backups(),
+            StartupDiskLibrary::DBus(lib) => lib.list_backups(),
+            StartupDiskLibrary::Image(lib) => lib.list_backups(),
+            StartupDiskLibrary::Mock(lib) => lib.list_backups(),
+        }
+    }
+    fn restore_backup(&self, name: &str) -> Result<()> {
+        match self {
+            StartupDiskLibrary::AsahiBless(lib) => lib.restore_backup(name),
+            StartupDiskLibrary::DBus(lib) => lib.restore_backup(name),
+            StartupDiskLibrary::Image(lib) => lib.restore_backup(name),
+            StartupDiskLibrary::Mock(lib) => lib.restore_backup(name),
+        }
+    }
+}
+
+/// Sets the boot volume, then reads it back to make sure the write took effect
+///
+/// On a mismatch the previous value is written back, so a failed write does not
+/// leave the NVRAM pointing somewhere unexpected. The NVRAM lock, on `device` if
+/// given, is held throughout, so another writer cannot change the boot volume
+/// between reading it back and rolling it back. The backends' own
+/// `set_boot_volume` therefore writes without locking.
+fn set_and_verify(
+    lib: &dyn StartupDiskTrait,
+    device: Option<&str>,
+    cand: &BootCandidate,
+    next: bool,
+) -> Result<()> {
+    let _lock = lock_nvram(device)?;
+    let previous = lib.get_boot_volume(next).ok();
+    lib.set_boot_volume(cand, next)?;
+
+    let current = lib.get_boot_volume(next)?;
+    if is_same_candidate(&current, cand) {
+        return Ok(());
+    }
+
+    let rolled_back = match previous {
+        Some(previous) if !is_same_candidate(&previous, &current) => {
+            lib.set_boot_volume(&previous, next).is_ok()
+        }
+        // Nothing changed, so there is nothing to undo
+        Some(_) => true,
+        None => false,
+    };
+    Err(Error::Verification {
+        found: current.vg_uuid.to_string(),
+        rolled_back,
+    })
+}
+
+/// Returns whether two candidates refer to the same volume group on the same partition
+pub fn is_same_candidate(a: &BootCandidate, b: &BootCandidate) -> bool {
+    a.part_uuid == b.part_uuid && a.vg_uuid == b.vg_uuid
+}
+
+/// Copies a boot candidate, which does not implement `Clone`
+pub fn copy_candidate(cand: &BootCandidate) -> BootCandidate {
+    BootCandidate {
+        vg_uuid: cand.vg_uuid,
+        volumes: cand
+            .volumes
+            .iter()
+            .map(|v| Volume {
+                name: v.name.clone(),
+                is_system: v.is_system,
+            })
+            .collect(),
+        part_uuid: cand.part_uuid,
+    }
+}
+
+/// Obtains access to `operation` up front if the backend does not already have it
+///
+/// This may prompt for authorization or re-run the process as root, so it should
+/// not be called on the main loop.
+pub fn escalate_if_needed(
+    startup_disk_library: &dyn StartupDiskTrait,
+    operation: Operation,
+) -> Result<()> {
+    match startup_disk_library.access(operation) {
+        Access::Granted => Ok(()),
+        Access::NeedsRoot | Access::NeedsAuthorization => startup_disk_library.authorize(operation),
+    }
+}
+
+/// Re-runs the process as root, for backends that access the hardware directly
+fn escalate_to_root() -> Result<()> {
+    sudo::escalate_if_needed().map_err(|e| Error::Escalation(e.to_string()))?;
+    Ok(())
+}
+
+pub fn get_vg_name(vg: &[Volume]) -> &str {
+    for v in vg {
+        if v.is_system {
+            return &v.name;
+        }
+    }
+    &vg[0].name
+}
+
+/// NVRAM image backend, configured once at startup
+static IMAGE_LIBRARY: OnceLock<Option<StartupDiskLibrary>> = OnceLock::new();
+
+/// Operates on an NVRAM image file instead of the NVRAM device
+///
+/// Boot candidates are read from `fixtures`, which defaults to the directory
+/// containing the image. Must be called before the library is first used.
+pub fn use_nvram_image(image: PathBuf, fixtures: Option<PathBuf>) {
+    let fixtures = fixtures.unwrap_or_else(|| {
+        image
+            .parent()
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+    });
+    let _ = IMAGE_LIBRARY.set(Some(StartupDiskLibrary::Image(image::ImageLibrary {
+        image,
+        fixtures,
+    })));
+}
+
+/// Returns the NVRAM image backend, if one was configured
+///
+/// Besides [`use_nvram_image`], the image can be selected with
+/// `USE_NVRAM_IMAGE` and the fixtures with `NVRAM_FIXTURES_DIR`.
+fn image_library() -> Option<&'static dyn StartupDiskTrait> {
+    if IMAGE_LIBRARY.get().is_none() {
+        if let Some(image) = env::var_os("USE_NVRAM_IMAGE") {
+            use_nvram_image(
+                PathBuf::from(image),
+                env::var_os("NVRAM_FIXTURES_DIR").map(PathBuf::from),
+            );
+        }
+    }
+
+    IMAGE_LIBRARY
+        .get_or_init(|| None)
+        .as_ref()
+        .map(|lib| lib as &dyn StartupDiskTrait)
+}
+
+/// Returns the library that accesses the hardware directly from this process
+pub fn local_library() -> &'static dyn StartupDiskTrait {
+    if let Some(startup_disk_library) = image_library() {
+        return startup_disk_library;
+    }
+
+    let use_mock_library = if cfg!(debug_assertions) {
+        env::var("USE_MOCK_LIBRARY").is_ok() || !is_asahi()
+    } else {
+        env::var("USE_MOCK_LIBRARY").is_ok()
+    };
+
+    // Create an instance of the chosen implementation
+    let startup_disk_library: &dyn StartupDiskTrait = if use_mock_library {
+        &StartupDiskLibrary::Mock(mock::MockLibrary)
+    } else {
+        &StartupDiskLibrary::AsahiBless(asahi::AsahiBlessLibrary)
+    };
+
+    startup_disk_library
+}
+
+/// Returns the D-Bus backend if the GUI goes through the helper
+///
+/// Unprivileged processes on Asahi go through the D-Bus helper; setting
+/// `USE_DBUS_HELPER` forces the helper, using the session bus if its value is
+/// `session`.
+fn dbus_library() -> Option<&'static dyn StartupDiskTrait> {
+    match env::var("USE_DBUS_HELPER").as_deref() {
+        Ok("session") => {
+            return Some(&StartupDiskLibrary::DBus(dbus::DBusLibrary {
+                session_bus: true,
+            }));
+        }
+        Ok(_) => {
+            return Some(&StartupDiskLibrary::DBus(dbus::DBusLibrary {
+                session_bus: false,
+            }));
+        }
+        Err(_) => {}
+    }
+
+    if env::var("USE_MOCK_LIBRARY").is_err()
+        && image_library().is_none()
+        && is_asahi()
+        && sudo::check() != sudo::RunningAs::Root
+    {
+        return Some(&StartupDiskLibrary::DBus(dbus::DBusLibrary {
+            session_bus: false,
+        }));
+    }
+
+    None
+}
+
+/// Whether [`startup_disk_library`] goes through the D-Bus helper
+///
+/// The helper only honors the NVRAM device set in the system-wide configuration.
+pub fn uses_dbus_helper() -> bool {
+    dbus_library().is_some()
+}
+
+/// Returns the library used by the GUI
+///
+/// This is the D-Bus helper for unprivileged processes on Asahi, and the
+/// [`local_library`] otherwise.
+pub fn startup_disk_library() -> &'static dyn StartupDiskTrait {
+    dbus_library().unwrap_or_else(local_library)
+}
+
+/// Serializes tests that share the environment, the mock state or the NVRAM lock
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(name: &str, is_system: bool) -> Volume {
+        Volume {
+            name: name.to_string(),
+            is_system,
+        }
+    }
+
+    fn candidate(vg: u128, part: u128) -> BootCandidate {
+        BootCandidate {
+            vg_uuid: Uuid::from_u128(vg),
+            part_uuid: Uuid::from_u128(part),
+            volumes: vec![volume("Macintosh HD", true)],
+        }
+    }
+
+    #[test]
+    fn same_candidate() {
+        assert!(is_same_candidate(&candidate(1, 2), &candidate(1, 2)));
+        assert!(!is_same_candidate(&candidate(1, 2), &candidate(1, 3)));
+        assert!(!is_same_candidate(&candidate(1, 2), &candidate(3, 2)));
+    }
+
+    #[test]
+    fn copied_candidate_is_same() {
+        let cand = candidate(1, 2);
+        let copy = copy_candidate(&cand);
+        assert!(is_same_candidate(&cand, &copy));
+        assert_eq!(copy.volumes.len(), 1);
+        assert_eq!(copy.volumes[0].name, "Macintosh HD");
+        assert!(copy.volumes[0].is_system);
+    }
+
+    #[test]
+    fn vg_name_prefers_system_volume() {
+        let volumes = [
+            volume("Data", false),
+            volume("Macintosh HD", true),
+            volume("Preboot", false),
+        ];
+        assert_eq!(get_vg_name(&volumes), "Macintosh HD");
+    }
+
+    #[test]
+    fn vg_name_falls_back_to_first_volume() {
+        let volumes = [volume("Data", false), volume("Preboot", false)];
+        assert_eq!(get_vg_name(&volumes), "Data");
+    }
+
+    #[test]
+    fn operation_names_round_trip() {
+        for operation in Operation::ALL {
+            assert_eq!(Operation::from_name(operation.name()), Some(operation));
+        }
+        assert_eq!(Operation::from_name("reboot"), None);
+    }
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MIT
+
+use asahi_bless::BootCandidate;
+use uuid::Uuid;
+
+use crate::startup_disk::backup::{self, Backup};
+use crate::startup_disk::current;
+use crate::startup_disk::device;
+use crate::startup_disk::Result;
+use crate::startup_disk::StartupDiskTrait;
+use crate::startup_disk::{escalate_to_root, is_asahi, Access, Operation};
+
+/// Disk scanned for boot candidates by asahi-bless
+static DISK_PATH: &str = "/dev/nvme0n1";
+
+pub struct AsahiBlessLibrary;
+impl StartupDiskTrait for AsahiBlessLibrary {
+    fn is_supported(&self) -> bool {
+        is_asahi()
+    }
+
+    fn access(&self, operation: Operation) -> Access {
+        if sudo::check() == sudo::RunningAs::Root {
+            return Access::Granted;
+        }
+
+        let has_access = match operation {
+            Operation::GetBootCandidates => device::can_read(DISK_PATH),
+            Operation::GetBootVolume | Operation::SetBootVolume => self
+                .nvram_device()
+                .is_ok_and(|device| device::can_write(&device)),
+            // Backups are kept in a directory only root can write
+            Operation::ListBackups | Operation::RestoreBackup => false,
+        };
+        if has_access {
+            Access::Granted
+        } else {
+            Access::NeedsRoot
+        }
+    }
+
+    fn authorize(&self, _operation: Operation) -> Result<()> {
+        escalate_to_root()
+    }
+
+    fn nvram_device(&self) -> Result<String> {
+        device::nvram_device()
+    }
+
+    fn current_part_uuid(&self) -> Option<Uuid> {
+        current::current_part_uuid()
+    }
+
+    fn get_boot_candidates(&self) -> Result<Vec<BootCandidate>> {
+        Ok(asahi_bless::get_boot_candidates()?)
+    }
+
+    fn get_boot_volume(&self, next: bool) -> Result<BootCandidate> {
+        Ok(asahi_bless::get_boot_volume(&self.nvram_device()?, next)?)
+    }
+
+    /// Writes without locking; the caller holds the NVRAM lock
+    fn set_boot_volume(&self, cand: &BootCandidate, next: bool) -> Result<()> {
+        let device = self.nvram_device()?;
+        backup::create_backup(&device)?;
+        Ok(asahi_bless::set_boot_volume(&device, cand, next)?)
+    }
+
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        backup::list_backups()
+    }
+
+    fn restore_backup(&self, name: &str) -> Result<()> {
+        backup::restore_backup(&self.nvram_device()?, name)
+    }
+}
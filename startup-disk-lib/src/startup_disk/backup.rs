@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: MIT
+
+use apple_nvram::NvramWriter;
+use glib::{self, ChecksumType};
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions, Permissions};
+use std::io::{self, Read};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::startup_disk::lock::lock_nvram;
+use crate::startup_disk::{Error, Result};
+
+/// Number of backups kept; older ones are deleted when a new one is made
+const MAX_BACKUPS: usize = 10;
+
+/// A raw snapshot of the NVRAM contents
+pub struct Backup {
+    /// File name of the snapshot, which also identifies it
+    pub name: String,
+    /// Creation time in seconds since the Unix epoch
+    pub timestamp: i64,
+    pub size: u64,
+    /// SHA-256 of the contents, as recorded when the backup was made
+    pub checksum: String,
+}
+
+/// Returns the backup directory
+///
+/// `STARTUP_DISK_BACKUP_DIR` overrides the default, which is
+/// `/var/lib/startup-disk/backups` for root and the user data directory otherwise.
+fn backup_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("STARTUP_DISK_BACKUP_DIR") {
+        PathBuf::from(dir)
+    } else if sudo::check() == sudo::RunningAs::Root {
+        PathBuf::from("/var/lib/startup-disk/backups")
+    } else {
+        glib::user_data_dir().join("startup-disk").join("backups")
+    }
+}
+
+fn backup_error(message: impl std::fmt::Display) -> Error {
+    Error::Backup(message.to_string())
+}
+
+/// Creates the backup directory, readable only by its owner
+///
+/// The NVRAM can hold sensitive variables, so neither the directory nor the
+/// backups in it are readable by other users. An existing directory is
+/// restricted as well.
+fn create_backup_dir(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    fs::set_permissions(dir, Permissions::from_mode(0o700))
+}
+
+/// Writes a file readable only by its owner
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    io::Write::write_all(&mut file, contents)
+}
+
+fn checksum(data: &[u8]) -> String {
+    glib::compute_checksum_for_data(ChecksumType::Sha256, data)
+        .unwrap()
+        .to_string()
+}
+
+fn checksum_path(path: &Path) -> PathBuf {
+    path.with_extension("sha256")
+}
+
+/// Reads a backup and verifies it against its recorded checksum
+fn read_backup(dir: &Path, backup: &Backup) -> Result<Vec<u8>> {
+    let data = fs::read(dir.join(&backup.name)).map_err(backup_error)?;
+    if checksum(&data) != backup.checksum {
+        return Err(backup_error(format!(
+            "Backup {} does not match its checksum",
+            backup.name
+        )));
+    }
+    Ok(data)
+}
+
+/// Lists the backups, newest first
+pub fn list_backups() -> Result<Vec<Backup>> {
+    let dir = backup_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(backup_error(e)),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(backup_error)?.path();
+        if path.extension().is_none_or(|ext| ext != "bin") {
+            continue;
+        }
+
+        // Checksum files use the sha256sum format
+        let Ok(checksum) = fs::read_to_string(checksum_path(&path)) else {
+            continue;
+        };
+        let metadata = fs::metadata(&path).map_err(backup_error)?;
+        let timestamp = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs() as i64);
+
+        backups.push(Backup {
+            name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            timestamp,
+            size: metadata.len(),
+            checksum: checksum.split_whitespace().next().unwrap_or("").to_string(),
+        });
+    }
+
+    // Names embed the creation time, so they sort chronologically
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+/// Snapshots the raw NVRAM contents, then prunes all but the newest backups
+pub fn create_backup(device: &str) -> Result<Backup> {
+    let mut data = Vec::new();
+    fs::File::open(device)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(backup_error)?;
+
+    let dir = backup_dir();
+    create_backup_dir(&dir).map_err(backup_error)?;
+
+    let now = glib::DateTime::now_utc().map_err(backup_error)?;
+    let name = format!(
+        "nvram-{}.bin",
+        now.format("%Y%m%dT%H%M%S.%fZ").map_err(backup_error)?
+    );
+    let path = dir.join(&name);
+    let checksum = checksum(&data);
+    write_private(&path, &data).map_err(backup_error)?;
+    write_private(
+        &checksum_path(&path),
+        format!("{checksum}  {name}\n").as_bytes(),
+    )
+    .map_err(backup_error)?;
+
+    for old in list_backups()?.iter().skip(MAX_BACKUPS) {
+        let old_path = dir.join(&old.name);
+        fs::remove_file(checksum_path(&old_path)).map_err(backup_error)?;
+        fs::remove_file(old_path).map_err(backup_error)?;
+    }
+
+    Ok(Backup {
+        name,
+        timestamp: now.to_unix(),
+        size: data.len() as u64,
+        checksum,
+    })
+}
+
+/// Writes a backup back to the NVRAM device
+///
+/// The backup must match its checksum and parse as an NVRAM image. The current
+/// contents are backed up first, so a restore can itself be undone.
+pub fn restore_backup(device: &str, name: &str) -> Result<()> {
+    let dir = backup_dir();
+    let backup = list_backups()?
+        .into_iter()
+        .find(|backup| backup.name == name)
+        .ok_or_else(|| backup_error(format!("No backup named {name}")))?;
+    let data = read_backup(&dir, &backup)?;
+    apple_nvram::nvram_parse(&data).map_err(asahi_bless::Error::from)?;
+
+    let _lock = lock_nvram(Some(device))?;
+    create_backup(device)?;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(device)
+        .map_err(asahi_bless::Error::ApplyError)?;
+    file.erase_if_needed(0, data.len());
+    NvramWriter::write_all(&mut file, 0, &data).map_err(asahi_bless::Error::ApplyError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::startup_disk::TEST_LOCK;
+
+    #[test]
+    fn backups_are_private() {
+        let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("startup-disk-backup-{}", std::process::id()));
+        let device = dir.join("nvram");
+        let backups = dir.join("backups");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&device, b"NVRAM contents").unwrap();
+        env::set_var("STARTUP_DISK_BACKUP_DIR", &backups);
+
+        let backup = create_backup(device.to_str().unwrap()).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&backups), 0o700);
+        assert_eq!(mode(&backups.join(&backup.name)), 0o600);
+        assert_eq!(mode(&checksum_path(&backups.join(&backup.name))), 0o600);
+
+        let listed = list_backups().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, backup.name);
+        assert_eq!(
+            read_backup(&backups, &listed[0]).unwrap(),
+            b"NVRAM contents"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// PARTUUID of the EFI system partition m1n1 loaded the running system from
+static ESP_PROPERTY: &str = "/proc/device-tree/chosen/asahi,efi-system-partition";
+
+static BY_PARTUUID_DIR: &str = "/dev/disk/by-partuuid";
+
+/// Splits a partition device name such as `nvme0n1p5` into `("nvme0n1p", 5)`
+fn split_partition_number(name: &str) -> Option<(&str, u32)> {
+    let disk = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    Some((disk, name[disk.len()..].parse().ok()?))
+}
+
+fn partition_device(by_partuuid: &Path, part_uuid: &str) -> Option<PathBuf> {
+    fs::canonicalize(by_partuuid.join(part_uuid.to_lowercase())).ok()
+}
+
+/// Returns the partition UUID of the partition right before the ESP
+fn stub_part_uuid(by_partuuid: &Path, esp: &str) -> Option<Uuid> {
+    let esp_device = partition_device(by_partuuid, esp.trim_end_matches('\0').trim())?;
+    let (disk, number) = split_partition_number(esp_device.file_name()?.to_str()?)?;
+    let stub_device = esp_device.with_file_name(format!("{disk}{}", number.checked_sub(1)?));
+
+    fs::read_dir(by_partuuid)
+        .ok()?
+        .flatten()
+        .find(|entry| fs::canonicalize(entry.path()).is_ok_and(|path| path == stub_device))
+        .and_then(|entry| Uuid::parse_str(entry.file_name().to_str()?).ok())
+}
+
+/// Returns the partition UUID of the stub the running system was booted from
+///
+/// The Asahi installer places each install's APFS stub container right before
+/// its EFI system partition, which m1n1 reports in the device tree.
+pub fn current_part_uuid() -> Option<Uuid> {
+    let esp = fs::read_to_string(ESP_PROPERTY).ok()?;
+    stub_part_uuid(Path::new(BY_PARTUUID_DIR), &esp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix;
+
+    #[test]
+    fn splits_partition_numbers() {
+        assert_eq!(split_partition_number("nvme0n1p5"), Some(("nvme0n1p", 5)));
+        assert_eq!(split_partition_number("nvme0n1p12"), Some(("nvme0n1p", 12)));
+        assert_eq!(split_partition_number("sda3"), Some(("sda", 3)));
+        assert_eq!(split_partition_number("sda"), None);
+    }
+
+    /// Creates `/dev` and `/dev/disk/by-partuuid` stand-ins holding the given
+    /// partitions, and returns the by-partuuid directory
+    fn partitions(name: &str, parts: &[(&str, u128)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("startup-disk-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dev = dir.join("dev");
+        let by_partuuid = dir.join("by-partuuid");
+        fs::create_dir_all(&dev).unwrap();
+        fs::create_dir_all(&by_partuuid).unwrap();
+        for &(device, uuid) in parts {
+            fs::write(dev.join(device), b"").unwrap();
+            unix::fs::symlink(
+                dev.join(device),
+                by_partuuid.join(Uuid::from_u128(uuid).to_string()),
+            )
+            .unwrap();
+        }
+        by_partuuid
+    }
+
+    fn esp(uuid: u128) -> String {
+        // m1n1 writes the property in upper case, with a trailing NUL
+        format!("{}\0", Uuid::from_u128(uuid).to_string().to_uppercase())
+    }
+
+    #[test]
+    fn stub_before_nvme_esp() {
+        let by_partuuid = partitions("nvme", &[("nvme0n1p4", 4), ("nvme0n1p5", 5)]);
+        assert_eq!(
+            stub_part_uuid(&by_partuuid, &esp(5)),
+            Some(Uuid::from_u128(4))
+        );
+        fs::remove_dir_all(by_partuuid.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn stub_before_sd_esp() {
+        let by_partuuid = partitions("sd", &[("sda1", 1), ("sda2", 2), ("sda3", 3)]);
+        assert_eq!(
+            stub_part_uuid(&by_partuuid, &esp(3)),
+            Some(Uuid::from_u128(2))
+        );
+        fs::remove_dir_all(by_partuuid.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn no_stub_partition() {
+        // The partition before the ESP is missing, or there is none
+        let by_partuuid = partitions("missing", &[("nvme0n1p1", 1), ("nvme0n1p5", 5)]);
+        assert_eq!(stub_part_uuid(&by_partuuid, &esp(5)), None);
+        assert_eq!(stub_part_uuid(&by_partuuid, &esp(1)), None);
+        // The ESP itself is unknown
+        assert_eq!(stub_part_uuid(&by_partuuid, &esp(9)), None);
+        fs::remove_dir_all(by_partuuid.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+// SPDX-License-Identifier: MIT
+
+use asahi_bless::{BootCandidate, Volume};
+use gio::{self, BusType, DBusCallFlags, DBusConnection, DBusError};
+use glib::{self, prelude::*, Variant, VariantTy};
+use std::io;
+use uuid::Uuid;
+
+use crate::startup_disk::current;
+use crate::startup_disk::StartupDiskTrait;
+use crate::startup_disk::{Access, Backup, Error, Operation, Result};
+
+pub static BUS_NAME: &str = "org.startup_disk.StartupDisk.Helper";
+pub static OBJECT_PATH: &str = "/org/startup_disk/StartupDisk/Helper";
+pub static INTERFACE_NAME: &str = "org.startup_disk.StartupDisk.Helper";
+
+pub static INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.startup_disk.StartupDisk.Helper">
+    <method name="GetAccess">
+      <arg type="s" name="operation" direction="in"/>
+      <arg type="b" name="authorized" direction="out"/>
+    </method>
+    <method name="Authorize">
+      <arg type="s" name="operation" direction="in"/>
+    </method>
+    <method name="GetBootCandidates">
+      <arg type="a(ssa(sb))" name="candidates" direction="out"/>
+    </method>
+    <method name="GetNvramDevice">
+      <arg type="s" name="device" direction="out"/>
+    </method>
+    <method name="GetBootVolume">
+      <arg type="b" name="next" direction="in"/>
+      <arg type="(ssa(sb))" name="candidate" direction="out"/>
+    </method>
+    <method name="SetBootVolume">
+      <arg type="(ssa(sb))" name="candidate" direction="in"/>
+      <arg type="b" name="next" direction="in"/>
+    </method>
+    <method name="ListBackups">
+      <arg type="a(sxts)" name="backups" direction="out"/>
+    </method>
+    <method name="RestoreBackup">
+      <arg type="s" name="name" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Prefix for the D-Bus error names returned by the helper
+pub static ERROR_PREFIX: &str = "org.startup_disk.StartupDisk.Helper.Error";
+
+/// Wire representation of a boot candidate: (vg_uuid, part_uuid, [(name, is_system)])
+pub type CandidateTuple = (String, String, Vec<(String, bool)>);
+
+pub fn candidate_to_tuple(cand: &BootCandidate) -> CandidateTuple {
+    (
+        cand.vg_uuid.to_string(),
+        cand.part_uuid.to_string(),
+        cand.volumes
+            .iter()
+            .map(|v| (v.name.clone(), v.is_system))
+            .collect(),
+    )
+}
+
+pub fn candidate_from_tuple(tuple: CandidateTuple) -> Option<BootCandidate> {
+    let (vg_uuid, part_uuid, volumes) = tuple;
+    Some(BootCandidate {
+        vg_uuid: Uuid::parse_str(&vg_uuid).ok()?,
+        part_uuid: Uuid::parse_str(&part_uuid).ok()?,
+        volumes: volumes
+            .into_iter()
+            .map(|(name, is_system)| Volume { name, is_system })
+            .collect(),
+    })
+}
+
+/// Wire representation of a backup: (name, timestamp, size, checksum)
+pub type BackupTuple = (String, i64, u64, String);
+
+pub fn backup_to_tuple(backup: &Backup) -> BackupTuple {
+    (
+        backup.name.clone(),
+        backup.timestamp,
+        backup.size,
+        backup.checksum.clone(),
+    )
+}
+
+fn backup_from_tuple(tuple: BackupTuple) -> Backup {
+    let (name, timestamp, size, checksum) = tuple;
+    Backup {
+        name,
+        timestamp,
+        size,
+        checksum,
+    }
+}
+
+/// Maps a backend error to the D-Bus error name and message sent to clients
+pub fn error_to_dbus(error: &Error) -> (String, String) {
+    let name = match error {
+        Error::Bless(asahi_bless::Error::Parse) => "Parse",
+        Error::Bless(asahi_bless::Error::SectionTooBig) => "SectionTooBig",
+        Error::Bless(asahi_bless::Error::ApplyError(_)) => "ApplyError",
+        Error::Bless(asahi_bless::Error::OutOfRange) => "OutOfRange",
+        Error::Bless(asahi_bless::Error::Ambiguous) => "Ambiguous",
+        Error::Bless(asahi_bless::Error::NvramReadError(_)) => "NvramReadError",
+        Error::Bless(asahi_bless::Error::DiskReadError(_)) => "DiskReadError",
+        Error::Bless(asahi_bless::Error::VolumeNotFound) => "VolumeNotFound",
+        Error::Escalation(_) => "NotAuthorized",
+        // Labels and panics are local to the process and never reach the helper
+        Error::Helper(_) | Error::Labels(_) | Error::Internal(_) => "Failed",
+        Error::Busy => "Busy",
+        Error::NoNvramDevice => "NoNvramDevice",
+        Error::Config(_) => "Config",
+        Error::Lock(_) => "Lock",
+        Error::Backup(_) => "Backup",
+        Error::Verification {
+            rolled_back: true, ..
+        } => "VerificationFailed",
+        Error::Verification {
+            rolled_back: false, ..
+        } => "VerificationFailedNotRolledBack",
+    };
+    let message = match error {
+        Error::Bless(
+            asahi_bless::Error::ApplyError(e)
+            | asahi_bless::Error::NvramReadError(e)
+            | asahi_bless::Error::DiskReadError(e),
+        ) => e.to_string(),
+        Error::Escalation(message)
+        | Error::Helper(message)
+        | Error::Config(message)
+        | Error::Lock(message)
+        | Error::Backup(message) => message.clone(),
+        Error::Verification { found, .. } => found.clone(),
+        _ => error.to_string(),
+    };
+    (format!("{ERROR_PREFIX}.{name}"), message)
+}
+
+/// Maps an error received over D-Bus back to a backend error
+fn error_from_dbus(mut error: glib::Error) -> Error {
+    let remote = DBusError::remote_error(&error);
+    DBusError::strip_remote_error(&mut error);
+    let message = error.message().to_string();
+
+    let name = remote
+        .as_deref()
+        .and_then(|name| name.strip_prefix(ERROR_PREFIX))
+        .and_then(|name| name.strip_prefix('.'));
+    let error = match name {
+        Some("Parse") => asahi_bless::Error::Parse,
+        Some("SectionTooBig") => asahi_bless::Error::SectionTooBig,
+        Some("ApplyError") => asahi_bless::Error::ApplyError(io::Error::other(message)),
+        Some("OutOfRange") => asahi_bless::Error::OutOfRange,
+        Some("Ambiguous") => asahi_bless::Error::Ambiguous,
+        Some("NvramReadError") => asahi_bless::Error::NvramReadError(io::Error::other(message)),
+        Some("DiskReadError") => asahi_bless::Error::DiskReadError(io::Error::other(message)),
+        Some("VolumeNotFound") => asahi_bless::Error::VolumeNotFound,
+        Some("NotAuthorized") => return Error::Escalation(message),
+        Some("Busy") => return Error::Busy,
+        Some("NoNvramDevice") => return Error::NoNvramDevice,
+        Some("Config") => return Error::Config(message),
+        Some("Lock") => return Error::Lock(message),
+        Some("Backup") => return Error::Backup(message),
+        Some("VerificationFailed") => {
+            return Error::Verification {
+                found: message,
+                rolled_back: true,
+            }
+        }
+        Some("VerificationFailedNotRolledBack") => {
+            return Error::Verification {
+                found: message,
+                rolled_back: false,
+            }
+        }
+        _ => return Error::Helper(message),
+    };
+    Error::Bless(error)
+}
+
+fn invalid_reply() -> Error {
+    Error::Helper("Invalid reply from the helper service".to_string())
+}
+
+/// Backend that forwards every call to the privileged helper over D-Bus
+pub struct DBusLibrary {
+    pub session_bus: bool,
+}
+
+impl DBusLibrary {
+    fn call(
+        &self,
+        method: &str,
+        parameters: Option<&Variant>,
+        reply_type: &VariantTy,
+    ) -> std::result::Result<Variant, glib::Error> {
+        let bus_type = if self.session_bus {
+            BusType::Session
+        } else {
+            BusType::System
+        };
+        let connection: DBusConnection = gio::bus_get_sync(bus_type, gio::Cancellable::NONE)?;
+
+        // Allow plenty of time for the user to answer the polkit prompt
+        connection.call_sync(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            INTERFACE_NAME,
+            method,
+            parameters,
+            Some(reply_type),
+            DBusCallFlags::ALLOW_INTERACTIVE_AUTHORIZATION,
+            i32::MAX,
+            gio::Cancellable::NONE,
+        )
+    }
+}
+
+impl StartupDiskTrait for DBusLibrary {
+    fn is_supported(&self) -> bool {
+        true
+    }
+
+    fn access(&self, operation: Operation) -> Access {
+        let authorized = self
+            .call(
+                "GetAccess",
+                Some(&(operation.name(),).to_variant()),
+                &<(bool,)>::static_variant_type(),
+            )
+            .ok()
+            .and_then(|reply| reply.get::<(bool,)>());
+        match authorized {
+            Some((true,)) => Access::Granted,
+            _ => Access::NeedsAuthorization,
+        }
+    }
+
+    fn authorize(&self, operation: Operation) -> Result<()> {
+        self.call(
+            "Authorize",
+            Some(&(operation.name(),).to_variant()),
+            VariantTy::UNIT,
+        )
+        .map_err(error_from_dbus)?;
+
+        Ok(())
+    }
+
+    fn nvram_device(&self) -> Result<String> {
+        let reply = self
+            .call("GetNvramDevice", None, &<(String,)>::static_variant_type())
+            .map_err(error_from_dbus)?;
+        let (device,) = reply.get::<(String,)>().ok_or_else(invalid_reply)?;
+
+        Ok(device)
+    }
+
+    fn current_part_uuid(&self) -> Option<Uuid> {
+        // The device tree and partition links are world-readable, so there is
+        // no need to go through the helper
+        current::current_part_uuid()
+    }
+
+    fn get_boot_candidates(&self) -> Result<Vec<BootCandidate>> {
+        let reply = self
+            .call(
+                "GetBootCandidates",
+                None,
+                &<(Vec<CandidateTuple>,)>::static_variant_type(),
+            )
+            .map_err(error_from_dbus)?;
+        let (cands,) = reply
+            .get::<(Vec<CandidateTuple>,)>()
+            .ok_or_else(invalid_reply)?;
+
+        cands
+            .into_iter()
+            .map(|cand| candidate_from_tuple(cand).ok_or_else(invalid_reply))
+            .collect()
+    }
+
+    fn get_boot_volume(&self, next: bool) -> Result<BootCandidate> {
+        let reply = self
+            .call(
+                "GetBootVolume",
+                Some(&(next,).to_variant()),
+                &<(CandidateTuple,)>::static_variant_type(),
+            )
+            .map_err(error_from_dbus)?;
+        let (cand,) = reply.get::<(CandidateTuple,)>().ok_or_else(invalid_reply)?;
+
+        candidate_from_tuple(cand).ok_or_else(invalid_reply)
+    }
+
+    fn set_boot_volume(&self, cand: &BootCandidate, next: bool) -> Result<()> {
+        self.call(
+            "SetBootVolume",
+            Some(&(candidate_to_tuple(cand), next).to_variant()),
+            VariantTy::UNIT,
+        )
+        .map_err(error_from_dbus)?;
+
+        Ok(())
+    }
+
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        let reply = self
+            .call(
+                "ListBackups",
+                None,
+                &<(Vec<BackupTuple>,)>::static_variant_type(),
+            )
+            .map_err(error_from_dbus)?;
+        let (backups,) = reply
+            .get::<(Vec<BackupTuple>,)>()
+            .ok_or_else(invalid_reply)?;
+
+        Ok(backups.into_iter().map(backup_from_tuple).collect())
+    }
+
+    fn restore_backup(&self, name: &str) -> Result<()> {
+        self.call(
+            "RestoreBackup",
+            Some(&(name,).to_variant()),
+            VariantTy::UNIT,
+        )
+        .map_err(error_from_dbus)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(error: &Error) -> Error {
+        let (name, message) = error_to_dbus(error);
+        error_from_dbus(DBusError::new_for_dbus_error(&name, &message))
+    }
+
+    #[test]
+    fn errors_survive_dbus() {
+        let errors = [
+            Error::Bless(asahi_bless::Error::Parse),
+            Error::Bless(asahi_bless::Error::SectionTooBig),
+            Error::Bless(asahi_bless::Error::ApplyError(io::Error::other("write"))),
+            Error::Bless(asahi_bless::Error::OutOfRange),
+            Error::Bless(asahi_bless::Error::Ambiguous),
+            Error::Bless(asahi_bless::Error::NvramReadError(io::Error::other("read"))),
+            Error::Bless(asahi_bless::Error::DiskReadError(io::Error::other("disk"))),
+            Error::Bless(asahi_bless::Error::VolumeNotFound),
+            Error::Escalation("denied".to_string()),
+            Error::Helper("gone".to_string()),
+            Error::Busy,
+            Error::NoNvramDevice,
+            Error::Config("invalid".to_string()),
+            Error::Lock("read-only".to_string()),
+            Error::Backup("full".to_string()),
+            Error::Verification {
+                found: Uuid::nil().to_string(),
+                rolled_back: true,
+            },
+            Error::Verification {
+                found: Uuid::nil().to_string(),
+                rolled_back: false,
+            },
+        ];
+
+        for error in errors {
+            let received = round_trip(&error);
+            assert_eq!(error_to_dbus(&received), error_to_dbus(&error));
+            assert_eq!(received.to_string(), error.to_string());
+        }
+    }
+
+    #[test]
+    fn local_errors_become_helper_errors() {
+        for error in [
+            Error::Labels("invalid".to_string()),
+            Error::Internal("panicked".to_string()),
+        ] {
+            assert!(matches!(round_trip(&error), Error::Helper(_)));
+        }
+    }
+
+    #[test]
+    fn unknown_errors_become_helper_errors() {
+        let error = DBusError::new_for_dbus_error("org.freedesktop.DBus.Error.NoReply", "timeout");
+        match error_from_dbus(error) {
+            Error::Helper(message) => assert_eq!(message, "timeout"),
+            error => panic!("unexpected error: {error:?}"),
+        }
+    }
+
+    #[test]
+    fn candidates_survive_dbus() {
+        let cand = BootCandidate {
+            vg_uuid: Uuid::from_u128(1),
+            part_uuid: Uuid::from_u128(2),
+            volumes: vec![
+                Volume {
+                    name: "Macintosh HD".to_string(),
+                    is_system: true,
+                },
+                Volume {
+                    name: "Data".to_string(),
+                    is_system: false,
+                },
+            ],
+        };
+
+        let received = candidate_from_tuple(candidate_to_tuple(&cand)).unwrap();
+        assert_eq!(candidate_to_tuple(&received), candidate_to_tuple(&cand));
+    }
+
+    #[test]
+    fn invalid_candidate_uuid() {
+        let tuple = ("not a uuid".to_string(), Uuid::nil().to_string(), vec![]);
+        assert!(candidate_from_tuple(tuple).is_none());
+    }
+}
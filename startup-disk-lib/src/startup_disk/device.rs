@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: MIT
+
+use serde::Deserialize;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::startup_disk::{Error, Result};
+
+/// Where the Asahi kernel exposes the NVRAM partition by name
+static BY_NAME_PATH: &str = "/dev/mtd/by-name/nvram";
+
+/// Name of the NVRAM partition in `/proc/mtd`
+static MTD_NAME: &str = "nvram";
+
+/// System-wide configuration, only writable by root
+static CONFIG_PATH: &str = "/etc/startup-disk/config.toml";
+
+/// Device given on the command line, which takes precedence over everything else
+static DEVICE_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Config {
+    nvram_device: Option<String>,
+}
+
+/// Whether this process can open `path` for reading
+pub fn can_read(path: &str) -> bool {
+    File::open(path).is_ok()
+}
+
+/// Whether this process can open `path` for reading and writing
+///
+/// asahi-bless opens the NVRAM read-write even to read it, so this is what
+/// both reading and writing the boot volume need.
+pub fn can_write(path: &str) -> bool {
+    OpenOptions::new().read(true).write(true).open(path).is_ok()
+}
+
+/// Uses `device` as the NVRAM device; must be called before it is first looked up
+pub fn use_nvram_device(device: String) {
+    let _ = DEVICE_OVERRIDE.set(device);
+}
+
+fn config_device() -> Result<Option<String>> {
+    let contents = match fs::read_to_string(CONFIG_PATH) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Config(format!("{CONFIG_PATH}: {e}"))),
+    };
+    let config: Config = toml::from_str(&contents)
+        .map_err(|e| Error::Config(format!("Invalid {CONFIG_PATH}: {e}")))?;
+
+    Ok(config.nvram_device)
+}
+
+/// Finds the NVRAM partition in the `/proc/mtd` table
+fn proc_mtd_device() -> Option<String> {
+    parse_proc_mtd(&fs::read_to_string("/proc/mtd").ok()?)
+}
+
+/// Returns the device of the NVRAM partition listed in `contents`
+///
+/// Lines look like `mtd3: 00100000 00002000 "nvram"`, after a header line.
+fn parse_proc_mtd(contents: &str) -> Option<String> {
+    contents.lines().skip(1).find_map(|line| {
+        let (dev, rest) = line.split_once(':')?;
+        let name = rest.split_whitespace().nth(2)?.trim_matches('"');
+        (name == MTD_NAME).then(|| format!("/dev/{dev}"))
+    })
+}
+
+/// Returns the NVRAM device node
+///
+/// In order of precedence, this is the `--nvram-device` flag,
+/// `STARTUP_DISK_NVRAM_DEVICE`, `nvram_device` in the configuration file,
+/// `/dev/mtd/by-name/nvram` and finally the `nvram` partition in `/proc/mtd`.
+pub fn nvram_device() -> Result<String> {
+    if let Some(device) = DEVICE_OVERRIDE.get() {
+        return Ok(device.clone());
+    }
+    if let Ok(device) = env::var("STARTUP_DISK_NVRAM_DEVICE") {
+        return Ok(device);
+    }
+    if let Some(device) = config_device()? {
+        return Ok(device);
+    }
+    if Path::new(BY_NAME_PATH).exists() {
+        return Ok(BY_NAME_PATH.to_string());
+    }
+
+    proc_mtd_device().ok_or(Error::NoNvramDevice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_nvram_partition() {
+        let contents = "dev:    size   erasesize  name
+mtd0: 00100000 00001000 \"1TR\"
+mtd1: 00100000 00002000 \"nvram\"
+mtd2: 00400000 00001000 \"system\"
+";
+        assert_eq!(parse_proc_mtd(contents).as_deref(), Some("/dev/mtd1"));
+    }
+
+    #[test]
+    fn no_nvram_partition() {
+        assert_eq!(parse_proc_mtd(""), None);
+        assert_eq!(parse_proc_mtd("dev:    size   erasesize  name\n"), None);
+        assert_eq!(
+            parse_proc_mtd("dev:    size   erasesize  name\nmtd0: 00100000 00001000 \"1TR\"\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let contents = "dev:    size   erasesize  name
+garbage
+mtd0: 00100000
+mtd3: 00100000 00002000 \"nvram\"
+";
+        assert_eq!(parse_proc_mtd(contents).as_deref(), Some("/dev/mtd3"));
+    }
+}
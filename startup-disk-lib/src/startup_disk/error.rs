@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MIT
+
+use gettextrs::dgettext;
+use std::fmt;
+
+use crate::startup_disk::gettext_f;
+use crate::startup_disk::i18n::GETTEXT_DOMAIN;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Error reported by asahi-bless while accessing the NVRAM or the disk
+    Bless(asahi_bless::Error),
+    /// The privileges needed to access the hardware could not be obtained
+    Escalation(String),
+    /// The privileged helper could not be reached
+    Helper(String),
+    /// Another process is writing the NVRAM
+    Busy,
+    /// No NVRAM partition could be found
+    NoNvramDevice,
+    /// The system-wide configuration file could not be read
+    Config(String),
+    /// The NVRAM lock could not be taken for a reason other than another writer holding it
+    Lock(String),
+    /// An NVRAM backup could not be made or restored
+    Backup(String),
+    /// The user-defined labels could not be read or saved
+    Labels(String),
+    /// The NVRAM did not contain the new boot volume after writing it
+    Verification {
+        /// Volume group UUID read back from the NVRAM
+        found: String,
+        /// Whether the previous boot volume is in place again
+        rolled_back: bool,
+    },
+    /// A backend call panicked
+    Internal(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Error::Bless(e) => match e {
+                asahi_bless::Error::Parse => dgettext(GETTEXT_DOMAIN, "Failed to parse the NVRAM contents"),
+                asahi_bless::Error::SectionTooBig => dgettext(GETTEXT_DOMAIN, "The NVRAM section is too big"),
+                asahi_bless::Error::ApplyError(e) => gettext_f(
+                    "Failed to write the NVRAM: {error}",
+                    &[("error", &e.to_string())],
+                ),
+                asahi_bless::Error::OutOfRange => dgettext(GETTEXT_DOMAIN, "Value out of range"),
+                asahi_bless::Error::Ambiguous => dgettext(GETTEXT_DOMAIN, "More than one boot candidate matches"),
+                asahi_bless::Error::NvramReadError(e) => gettext_f(
+                    "Failed to read the NVRAM: {error}",
+                    &[("error", &e.to_string())],
+                ),
+                asahi_bless::Error::DiskReadError(e) => gettext_f(
+                    "Failed to read the disk: {error}",
+                    &[("error", &e.to_string())],
+                ),
+                asahi_bless::Error::VolumeNotFound => dgettext(GETTEXT_DOMAIN, "Volume not found"),
+            },
+            Error::Escalation(message) => gettext_f(
+                "Failed to obtain the required privileges: {error}",
+                &[("error", message)],
+            ),
+            Error::Helper(message) => gettext_f(
+                "Failed to reach the helper service: {error}",
+                &[("error", message)],
+            ),
+            Error::Busy => dgettext(
+                GETTEXT_DOMAIN,
+                "NVRAM busy: another program is changing the startup disk, try again later",
+            ),
+            Error::NoNvramDevice => dgettext(
+                GETTEXT_DOMAIN,
+                "No NVRAM partition found: neither /dev/mtd/by-name/nvram nor an \"nvram\" entry in /proc/mtd exists, set nvram_device in /etc/startup-disk/config.toml",
+            ),
+            Error::Config(message) => gettext_f(
+                "Failed to read the configuration: {error}",
+                &[("error", message)],
+            ),
+            Error::Lock(message) => {
+                gettext_f("Failed to lock the NVRAM: {error}", &[("error", message)])
+            }
+            Error::Backup(message) => {
+                gettext_f("NVRAM backup failed: {error}", &[("error", message)])
+            }
+            Error::Labels(message) => gettext_f(
+                "Failed to access the labels: {error}",
+                &[("error", message)],
+            ),
+            Error::Verification {
+                found,
+                rolled_back: true,
+            } => gettext_f(
+                "The startup disk did not change: the NVRAM points at {uuid} after writing it, the previous startup disk is still set",
+                &[("uuid", found)],
+            ),
+            Error::Verification {
+                found,
+                rolled_back: false,
+            } => gettext_f(
+                "The startup disk did not change: the NVRAM points at {uuid} after writing it and the previous startup disk could not be restored",
+                &[("uuid", found)],
+            ),
+            Error::Internal(message) => gettext_f(
+                "Startup Disk ran into an internal error: {error}",
+                &[("error", message)],
+            ),
+        };
+
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bless(
+                asahi_bless::Error::ApplyError(e)
+                | asahi_bless::Error::NvramReadError(e)
+                | asahi_bless::Error::DiskReadError(e),
+            ) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<asahi_bless::Error> for Error {
+    fn from(e: asahi_bless::Error) -> Self {
+        Error::Bless(e)
+    }
+}
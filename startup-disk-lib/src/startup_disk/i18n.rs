@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: MIT
+
+use gettextrs::{dgettext, dngettext};
+
+/// Text domain of the library's messages
+///
+/// Messages are looked up in this domain explicitly, so they are translated
+/// whatever text domain the application using the library has switched to.
+pub(crate) static GETTEXT_DOMAIN: &str = "startup-disk";
+
+/// Replaces `{name}` placeholders, which translators can move around freely
+fn substitute(mut message: String, args: &[(&str, &str)]) -> String {
+    for (name, value) in args {
+        message = message.replace(&format!("{{{name}}}"), value);
+    }
+    message
+}
+
+/// Translates a message and fills in its named placeholders
+pub fn gettext_f(msgid: &str, args: &[(&str, &str)]) -> String {
+    substitute(dgettext(GETTEXT_DOMAIN, msgid), args)
+}
+
+/// Translates a message whose form depends on `n`, and fills in its named
+/// placeholders, including `{n}` itself
+pub fn ngettext_f(msgid: &str, msgid_plural: &str, n: u32, args: &[(&str, &str)]) -> String {
+    let message = substitute(dngettext(GETTEXT_DOMAIN, msgid, msgid_plural, n), args);
+    message.replace("{n}", &n.to_string())
+}
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: MIT
+
+use asahi_bless::{BootCandidate, Volume};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::startup_disk::backup::{self, Backup};
+use crate::startup_disk::device;
+use crate::startup_disk::Error;
+use crate::startup_disk::Result;
+use crate::startup_disk::StartupDiskTrait;
+use crate::startup_disk::{escalate_to_root, Access, Operation};
+
+/// Backend that operates on an NVRAM image file instead of the NVRAM device
+///
+/// The boot volume is read and written with asahi-bless as on real hardware.
+/// Boot candidates come from a fixture directory laid out as
+/// `<part-uuid>/<vg-uuid>`, where each volume group file lists one volume per
+/// line as `system <name>` or `data <name>`.
+pub struct ImageLibrary {
+    pub image: PathBuf,
+    pub fixtures: PathBuf,
+}
+
+fn disk_read_error(e: std::io::Error) -> Error {
+    Error::Bless(asahi_bless::Error::DiskReadError(e))
+}
+
+/// Returns the UUID a fixture entry is named after, skipping anything else
+fn fixture_uuid(path: &Path) -> Option<Uuid> {
+    Uuid::parse_str(path.file_name()?.to_str()?).ok()
+}
+
+fn read_volume_group(path: &Path) -> Result<Vec<Volume>> {
+    fs::read_to_string(path)
+        .map_err(disk_read_error)?
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once(' ') {
+            Some(("system", name)) => Ok(Volume {
+                name: name.to_string(),
+                is_system: true,
+            }),
+            Some(("data", name)) => Ok(Volume {
+                name: name.to_string(),
+                is_system: false,
+            }),
+            _ => Err(disk_read_error(std::io::Error::other(format!(
+                "Invalid volume {line:?} in {}",
+                path.display()
+            )))),
+        })
+        .collect()
+}
+
+impl StartupDiskTrait for ImageLibrary {
+    fn is_supported(&self) -> bool {
+        true
+    }
+
+    fn access(&self, operation: Operation) -> Access {
+        let has_access = match operation {
+            Operation::GetBootCandidates | Operation::ListBackups => true,
+            Operation::GetBootVolume | Operation::SetBootVolume | Operation::RestoreBackup => self
+                .nvram_device()
+                .is_ok_and(|image| device::can_write(&image)),
+        };
+        if has_access {
+            Access::Granted
+        } else {
+            Access::NeedsRoot
+        }
+    }
+
+    fn authorize(&self, _operation: Operation) -> Result<()> {
+        escalate_to_root()
+    }
+
+    fn nvram_device(&self) -> Result<String> {
+        self.image.to_str().map(str::to_string).ok_or_else(|| {
+            Error::Bless(asahi_bless::Error::NvramReadError(std::io::Error::other(
+                "NVRAM image path is not valid UTF-8",
+            )))
+        })
+    }
+
+    fn current_part_uuid(&self) -> Option<Uuid> {
+        None
+    }
+
+    fn get_boot_candidates(&self) -> Result<Vec<BootCandidate>> {
+        let mut cands = Vec::new();
+        for part in fs::read_dir(&self.fixtures).map_err(disk_read_error)? {
+            let part = part.map_err(disk_read_error)?.path();
+            let Some(part_uuid) = fixture_uuid(&part).filter(|_| part.is_dir()) else {
+                continue;
+            };
+
+            for vg in fs::read_dir(&part).map_err(disk_read_error)? {
+                let vg = vg.map_err(disk_read_error)?.path();
+                let Some(vg_uuid) = fixture_uuid(&vg) else {
+                    continue;
+                };
+                let volumes = read_volume_group(&vg)?;
+                if volumes.is_empty() {
+                    continue;
+                }
+                cands.push(BootCandidate {
+                    vg_uuid,
+                    volumes,
+                    part_uuid,
+                });
+            }
+        }
+
+        // Directory order is arbitrary, so keep the list stable between runs
+        cands.sort_by_key(|cand| (cand.part_uuid, cand.vg_uuid));
+        Ok(cands)
+    }
+
+    fn get_boot_volume(&self, next: bool) -> Result<BootCandidate> {
+        Ok(asahi_bless::get_boot_volume(&self.nvram_device()?, next)?)
+    }
+
+    /// Writes without locking; the caller holds the NVRAM lock
+    fn set_boot_volume(&self, cand: &BootCandidate, next: bool) -> Result<()> {
+        let image = self.nvram_device()?;
+        backup::create_backup(&image)?;
+        Ok(asahi_bless::set_boot_volume(&image, cand, next)?)
+    }
+
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        backup::list_backups()
+    }
+
+    fn restore_backup(&self, name: &str) -> Result<()> {
+        backup::restore_backup(&self.nvram_device()?, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::startup_disk::{is_same_candidate, set_and_verify, TEST_LOCK};
+    use std::env;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../res/fixtures");
+
+    #[test]
+    fn get_set_get() {
+        let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("startup-disk-image-{}", std::process::id()));
+        let image = dir.join("nvram.bin");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(Path::new(FIXTURES).join("nvram.bin"), &image).unwrap();
+        env::set_var("STARTUP_DISK_BACKUP_DIR", dir.join("backups"));
+
+        let lib = ImageLibrary {
+            image,
+            fixtures: PathBuf::from(FIXTURES),
+        };
+        let cands = lib.get_boot_candidates().unwrap();
+        assert_eq!(cands.len(), 2);
+        let name = |cand: &BootCandidate| cand.volumes[0].name.clone();
+        let macos = cands.iter().find(|c| name(c) == "Macintosh HD").unwrap();
+        let asahi = cands.iter().find(|c| name(c) == "Asahi Linux").unwrap();
+
+        assert!(is_same_candidate(
+            &lib.get_boot_volume(false).unwrap(),
+            macos
+        ));
+        set_and_verify(&lib, Some(&lib.nvram_device().unwrap()), asahi, false).unwrap();
+        assert!(is_same_candidate(
+            &lib.get_boot_volume(false).unwrap(),
+            asahi
+        ));
+
+        // The next boot volume is separate from the persistent one
+        set_and_verify(&lib, Some(&lib.nvram_device().unwrap()), macos, true).unwrap();
+        assert!(is_same_candidate(
+            &lib.get_boot_volume(true).unwrap(),
+            macos
+        ));
+        assert!(is_same_candidate(
+            &lib.get_boot_volume(false).unwrap(),
+            asahi
+        ));
+        assert_eq!(lib.list_backups().unwrap().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: MIT
+
+use nix::unistd::User;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::startup_disk::{Error, Result};
+
+/// Icons offered when choosing one for a boot candidate
+pub static ICONS: &[&str] = &[
+    "drive-harddisk-system",
+    "start-here",
+    "computer",
+    "computer-laptop",
+    "emblem-system",
+    "applications-engineering",
+    "applications-games",
+    "drive-harddisk",
+];
+
+/// Name and icon the user gave a volume group, each replacing the detected one
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Label {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+impl Label {
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.icon.is_none()
+    }
+}
+
+/// User-defined labels, keyed by volume group UUID
+///
+/// They are stored per user, in `startup-disk/labels.toml` in the user
+/// configuration directory:
+///
+/// ```toml
+/// [labels.a1c4e7f0-2b5d-4e8a-9c1f-5d8b2e6a0c73]
+/// name = "Work"
+/// icon = "computer-laptop"
+/// ```
+#[derive(Serialize, Deserialize, Default)]
+pub struct Labels {
+    #[serde(default)]
+    labels: BTreeMap<String, Label>,
+}
+
+fn labels_error(message: impl std::fmt::Display) -> Error {
+    Error::Labels(message.to_string())
+}
+
+/// Returns the user who ran sudo
+///
+/// sudo clears the environment, so the home directory comes from the
+/// password database.
+fn sudo_user() -> Option<User> {
+    if sudo::check() != sudo::RunningAs::Root {
+        return None;
+    }
+    let user = env::var("SUDO_USER").ok()?;
+
+    User::from_name(&user).ok().flatten()
+}
+
+/// Returns the configuration directory of a user who ran sudo
+///
+/// This follows `g_get_user_config_dir()`, which would find root's instead:
+/// `XDG_CONFIG_HOME` if sudo kept it, and `~/.config` otherwise.
+fn sudo_config_dir(user: &User) -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| user.dir.join(".config"), PathBuf::from)
+}
+
+/// Returns the labels file
+///
+/// `STARTUP_DISK_LABELS` overrides the default. After escalating through sudo,
+/// this is still the labels file of the user who ran it.
+fn labels_path(sudo_user: Option<&User>) -> PathBuf {
+    if let Some(path) = env::var_os("STARTUP_DISK_LABELS") {
+        return PathBuf::from(path);
+    }
+
+    let config_dir = match sudo_user {
+        Some(user) => sudo_config_dir(user),
+        None => glib::user_config_dir(),
+    };
+    config_dir.join("startup-disk").join("labels.toml")
+}
+
+impl Labels {
+    /// Reads the labels, of which there are none until the file is first saved
+    pub fn load() -> Result<Labels> {
+        let path = labels_path(sudo_user().as_ref());
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Labels::default()),
+            Err(e) => return Err(labels_error(e)),
+        };
+
+        toml::from_str(&contents)
+            .map_err(|e| labels_error(format!("Invalid {}: {e}", path.display())))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let sudo_user = sudo_user();
+        let path = labels_path(sudo_user.as_ref());
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).map_err(labels_error)?;
+        let contents = toml::to_string(self).map_err(labels_error)?;
+        fs::write(&path, contents).map_err(labels_error)?;
+
+        // Leave the files to the user rather than root
+        if let Some(user) = sudo_user {
+            for path in [dir, &path] {
+                unix::fs::chown(path, Some(user.uid.as_raw()), Some(user.gid.as_raw()))
+                    .map_err(labels_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, vg_uuid: Uuid) -> Option<&Label> {
+        self.labels.get(&vg_uuid.to_string())
+    }
+
+    /// Sets the label of a volume group, forgetting it if it is empty
+    pub fn set(&mut self, vg_uuid: Uuid, label: Label) {
+        if label.is_empty() {
+            self.labels.remove(&vg_uuid.to_string());
+        } else {
+            self.labels.insert(vg_uuid.to_string(), label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::startup_disk::TEST_LOCK;
+    use nix::unistd::getuid;
+
+    #[test]
+    fn labels_round_trip() {
+        let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("startup-disk-labels-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        env::set_var("STARTUP_DISK_LABELS", dir.join("labels.toml"));
+
+        assert_eq!(Labels::load().unwrap().get(Uuid::from_u128(1)), None);
+
+        let work = Label {
+            name: Some("Work".to_string()),
+            icon: Some("computer-laptop".to_string()),
+        };
+        let mut labels = Labels::default();
+        labels.set(Uuid::from_u128(1), work.clone());
+        labels.set(
+            Uuid::from_u128(2),
+            Label {
+                name: None,
+                icon: Some("start-here".to_string()),
+            },
+        );
+        labels.save().unwrap();
+
+        let mut loaded = Labels::load().unwrap();
+        assert_eq!(loaded.get(Uuid::from_u128(1)), Some(&work));
+        assert_eq!(
+            loaded.get(Uuid::from_u128(2)).unwrap().icon.as_deref(),
+            Some("start-here")
+        );
+
+        // Empty labels are forgotten rather than saved
+        loaded.set(Uuid::from_u128(1), Label::default());
+        loaded.save().unwrap();
+        assert_eq!(Labels::load().unwrap().get(Uuid::from_u128(1)), None);
+
+        env::remove_var("STARTUP_DISK_LABELS");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sudo_user_labels_path() {
+        let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let user = User::from_uid(getuid()).unwrap().unwrap();
+        env::remove_var("STARTUP_DISK_LABELS");
+        let config_home = env::var_os("XDG_CONFIG_HOME");
+
+        env::remove_var("XDG_CONFIG_HOME");
+        assert_eq!(
+            labels_path(Some(&user)),
+            user.dir.join(".config/startup-disk/labels.toml")
+        );
+
+        env::set_var("XDG_CONFIG_HOME", "/srv/config");
+        assert_eq!(
+            labels_path(Some(&user)),
+            PathBuf::from("/srv/config/startup-disk/labels.toml")
+        );
+
+        env::set_var("STARTUP_DISK_LABELS", "/srv/labels.toml");
+        assert_eq!(labels_path(Some(&user)), PathBuf::from("/srv/labels.toml"));
+
+        env::remove_var("STARTUP_DISK_LABELS");
+        match config_home {
+            Some(dir) => env::set_var("XDG_CONFIG_HOME", dir),
+            None => env::remove_var("XDG_CONFIG_HOME"),
+        }
+    }
+}
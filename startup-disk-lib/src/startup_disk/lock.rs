@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: MIT
+
+use std::env;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+
+use crate::startup_disk::{Error, Result};
+
+/// Name of the lock file shared by every process writing the NVRAM
+static LOCK_FILE_NAME: &str = "startup-disk.lock";
+
+/// Advisory lock held for the duration of an NVRAM read-modify-write
+///
+/// The lock is released when this is dropped.
+pub struct NvramLock {
+    _files: Vec<File>,
+}
+
+/// Returns the well-known lock file path
+///
+/// Root uses `/run/lock`; unprivileged processes, which can only be using the
+/// mock library, use their runtime directory instead.
+fn lock_path() -> PathBuf {
+    if sudo::check() == sudo::RunningAs::Root {
+        PathBuf::from("/run/lock").join(LOCK_FILE_NAME)
+    } else {
+        env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(env::temp_dir)
+            .join(LOCK_FILE_NAME)
+    }
+}
+
+fn lock_error(e: std::io::Error) -> Error {
+    Error::Lock(e.to_string())
+}
+
+fn try_lock(file: File) -> Result<File> {
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(Error::Busy),
+        Err(TryLockError::Error(e)) => Err(lock_error(e)),
+    }
+}
+
+/// Takes an exclusive lock on the well-known lock file and, if given, the NVRAM device node
+///
+/// Fails with [`Error::Busy`] instead of waiting if another writer holds either lock.
+pub fn lock_nvram(device: Option<&str>) -> Result<NvramLock> {
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path())
+        .map_err(lock_error)?;
+    let mut files = vec![try_lock(lock_file)?];
+
+    if let Some(device) = device {
+        let device_file = File::open(device).map_err(lock_error)?;
+        files.push(try_lock(device_file)?);
+    }
+
+    Ok(NvramLock { _files: files })
+}
@@ -0,0 +1,560 @@
+// SPDX-License-Identifier: MIT
+
+use asahi_bless::{BootCandidate, Volume};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::startup_disk::copy_candidate;
+use crate::startup_disk::get_vg_name;
+use crate::startup_disk::Backup;
+use crate::startup_disk::Error;
+use crate::startup_disk::Result;
+use crate::startup_disk::StartupDiskTrait;
+use crate::startup_disk::{Access, Operation};
+
+/// Number of candidates generated when a scenario does not list any
+const GENERATED_CANDIDATES: usize = 3;
+
+/// A mock setup, loaded from the TOML file named by `USE_MOCK_LIBRARY`
+///
+/// Everything is optional: candidates are generated from `seed` when none are
+/// listed, and the default boot volume is the first candidate unless given.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Scenario {
+    /// Seed for the generated candidates; `MOCK_SEED` takes precedence
+    seed: Option<u64>,
+    candidates: Vec<CandidateSpec>,
+    /// Volume group UUID or name of the default boot volume
+    default: Option<String>,
+    /// Volume group UUID or name of the next boot volume
+    next: Option<String>,
+    /// Volume group UUID or name of the running system
+    current: Option<String>,
+    /// Drop writes while still reporting success
+    silent_write_failure: bool,
+    /// Faults to inject, by operation name; `MOCK_FAULTS` overrides these
+    faults: HashMap<String, Fault>,
+}
+
+/// A failure or delay injected into a mock method
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Fault {
+    /// Error to return, named after the `asahi_bless::Error` variant or `Busy`
+    error: Option<String>,
+    /// Only fail every Nth call; 0 and 1 both fail every call
+    every: u32,
+    /// Only fail once a write has succeeded
+    after_write: bool,
+    /// Delay before returning, whether or not the call fails
+    delay_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CandidateSpec {
+    vg_uuid: String,
+    part_uuid: String,
+    volumes: Vec<VolumeSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VolumeSpec {
+    name: String,
+    #[serde(default)]
+    system: bool,
+}
+
+/// State of the mock NVRAM, shared by every call in this process
+struct MockState {
+    candidates: Vec<BootCandidate>,
+    /// (vg_uuid, part_uuid) of the default boot volume
+    default: Option<(Uuid, Uuid)>,
+    /// (vg_uuid, part_uuid) of the next boot volume, if overridden
+    next: Option<(Uuid, Uuid)>,
+    /// (vg_uuid, part_uuid) of the running system
+    current: Option<(Uuid, Uuid)>,
+    silent_write_failure: bool,
+    faults: HashMap<Operation, Fault>,
+    /// Calls eligible for failure so far, by operation
+    calls: HashMap<Operation, u32>,
+    /// Whether any write has succeeded
+    written: bool,
+}
+
+static STATE: Mutex<Option<MockState>> = Mutex::new(None);
+
+fn scenario_error(message: impl std::fmt::Display) -> Error {
+    Error::Bless(asahi_bless::Error::DiskReadError(std::io::Error::other(
+        format!("Invalid mock scenario: {message}"),
+    )))
+}
+
+/// Builds the error an injected fault returns
+fn fault_error(name: &str) -> Option<Error> {
+    let io_error = || std::io::Error::other("Injected fault");
+    let error = match name {
+        "Parse" => asahi_bless::Error::Parse,
+        "SectionTooBig" => asahi_bless::Error::SectionTooBig,
+        "ApplyError" => asahi_bless::Error::ApplyError(io_error()),
+        "OutOfRange" => asahi_bless::Error::OutOfRange,
+        "Ambiguous" => asahi_bless::Error::Ambiguous,
+        "NvramReadError" => asahi_bless::Error::NvramReadError(io_error()),
+        "DiskReadError" => asahi_bless::Error::DiskReadError(io_error()),
+        "VolumeNotFound" => asahi_bless::Error::VolumeNotFound,
+        "Busy" => return Some(Error::Busy),
+        "Lock" => return Some(Error::Lock(io_error().to_string())),
+        _ => return None,
+    };
+    Some(Error::Bless(error))
+}
+
+/// Collects the faults from the scenario, `MOCK_FAULTS` and `MOCK_LATENCY_MS`
+///
+/// `MOCK_FAULTS` uses the same TOML syntax as the scenario's `[faults]` table, e.g.
+/// `get_boot_volume = { error = "NvramReadError", every = 2 }`.
+fn load_faults(mut faults: HashMap<String, Fault>) -> Result<HashMap<Operation, Fault>> {
+    if let Ok(overrides) = env::var("MOCK_FAULTS") {
+        let overrides: HashMap<String, Fault> =
+            toml::from_str(&overrides).map_err(scenario_error)?;
+        faults.extend(overrides);
+    }
+
+    let mut faults = faults
+        .into_iter()
+        .map(|(name, fault)| {
+            let operation = Operation::from_name(&name)
+                .ok_or_else(|| scenario_error(format!("unknown operation {name}")))?;
+            if let Some(error) = fault.error.as_deref().filter(|e| fault_error(e).is_none()) {
+                return Err(scenario_error(format!("unknown error {error}")));
+            }
+            Ok((operation, fault))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    if let Some(delay_ms) = env::var("MOCK_LATENCY_MS")
+        .ok()
+        .and_then(|delay_ms| delay_ms.parse().ok())
+    {
+        for operation in Operation::ALL {
+            let fault = faults.entry(operation).or_default();
+            if fault.delay_ms == 0 {
+                fault.delay_ms = delay_ms;
+            }
+        }
+    }
+
+    Ok(faults)
+}
+
+fn random_uuid(rng: &mut StdRng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.random()).into_uuid()
+}
+
+fn generate_random_volumes(
+    rng: &mut StdRng,
+    num_volumes: usize,
+    max_length: usize,
+    is_system: &[bool],
+) -> Vec<Volume> {
+    (0..num_volumes)
+        .map(|n| {
+            let string_length = rng.random_range(1..=max_length);
+            let name = {
+                (0..string_length)
+                    .map(|_| rng.random_range(b'a'..=b'z') as char)
+                    .collect()
+            };
+            Volume {
+                name,
+                is_system: is_system[n],
+            }
+        })
+        .collect()
+}
+
+fn generate_candidates(rng: &mut StdRng) -> Vec<BootCandidate> {
+    let is_system: [&[bool]; GENERATED_CANDIDATES] =
+        [&[true, false], &[false, true], &[false, false]];
+    is_system
+        .iter()
+        .map(|is_system| BootCandidate {
+            vg_uuid: random_uuid(rng),
+            volumes: generate_random_volumes(rng, 2, 10, is_system),
+            part_uuid: random_uuid(rng),
+        })
+        .collect()
+}
+
+fn candidate_from_spec(spec: &CandidateSpec) -> Result<BootCandidate> {
+    if spec.volumes.is_empty() {
+        return Err(scenario_error(format!(
+            "volume group {} has no volumes",
+            spec.vg_uuid
+        )));
+    }
+    Ok(BootCandidate {
+        vg_uuid: Uuid::parse_str(&spec.vg_uuid).map_err(scenario_error)?,
+        part_uuid: Uuid::parse_str(&spec.part_uuid).map_err(scenario_error)?,
+        volumes: spec
+            .volumes
+            .iter()
+            .map(|v| Volume {
+                name: v.name.clone(),
+                is_system: v.system,
+            })
+            .collect(),
+    })
+}
+
+/// Resolves a scenario reference to a volume group UUID or name
+fn find_candidate(candidates: &[BootCandidate], target: &str) -> Result<(Uuid, Uuid)> {
+    let uuid = Uuid::parse_str(target).ok();
+    candidates
+        .iter()
+        .find(|cand| match uuid {
+            Some(uuid) => cand.vg_uuid == uuid,
+            None => get_vg_name(&cand.volumes) == target,
+        })
+        .map(|cand| (cand.vg_uuid, cand.part_uuid))
+        .ok_or_else(|| scenario_error(format!("no candidate matches {target}")))
+}
+
+/// Loads the scenario named by `USE_MOCK_LIBRARY`, if it names a file
+fn load_scenario() -> Result<Scenario> {
+    let Some(path) = env::var_os("USE_MOCK_LIBRARY").filter(|path| Path::new(path).is_file())
+    else {
+        return Ok(Scenario::default());
+    };
+    let contents = fs::read_to_string(&path).map_err(scenario_error)?;
+    toml::from_str(&contents).map_err(scenario_error)
+}
+
+fn load_state() -> Result<MockState> {
+    let scenario = load_scenario()?;
+
+    let candidates = if scenario.candidates.is_empty() {
+        let seed = env::var("MOCK_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .or(scenario.seed);
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        generate_candidates(&mut rng)
+    } else {
+        scenario
+            .candidates
+            .iter()
+            .map(candidate_from_spec)
+            .collect::<Result<_>>()?
+    };
+
+    let default = match &scenario.default {
+        Some(target) => Some(find_candidate(&candidates, target)?),
+        None => candidates
+            .first()
+            .map(|cand| (cand.vg_uuid, cand.part_uuid)),
+    };
+    let next = scenario
+        .next
+        .as_deref()
+        .map(|target| find_candidate(&candidates, target))
+        .transpose()?;
+
+    let current = scenario
+        .current
+        .as_deref()
+        .map(|target| find_candidate(&candidates, target))
+        .transpose()?;
+
+    Ok(MockState {
+        candidates,
+        default,
+        next,
+        current,
+        silent_write_failure: scenario.silent_write_failure
+            || env::var("MOCK_SILENT_WRITE_FAILURE").is_ok(),
+        faults: load_faults(scenario.faults)?,
+        calls: HashMap::new(),
+        written: false,
+    })
+}
+
+/// Runs `f` on the mock state, loading the scenario on first use
+fn with_state<T>(f: impl FnOnce(&mut MockState) -> Result<T>) -> Result<T> {
+    let mut state = STATE.lock().unwrap();
+    if state.is_none() {
+        *state = Some(load_state()?);
+    }
+    f(state.as_mut().unwrap())
+}
+
+/// Makes the mock backend load its scenario and faults again on the next call
+///
+/// This is for tests that change `USE_MOCK_LIBRARY`, `MOCK_FAULTS` or
+/// `MOCK_SILENT_WRITE_FAILURE` from one call to the next.
+#[cfg(test)]
+pub(crate) fn reload_mock() {
+    *STATE.lock().unwrap() = None;
+}
+
+/// Applies the latency and failure configured for `operation`
+fn inject_fault(operation: Operation) -> Result<()> {
+    let (delay, error) = with_state(|state| {
+        let Some(fault) = state.faults.get(&operation) else {
+            return Ok((Duration::ZERO, None));
+        };
+
+        let mut error = None;
+        if let Some(name) = &fault.error {
+            if !fault.after_write || state.written {
+                let calls = state.calls.entry(operation).or_default();
+                *calls += 1;
+                if *calls % fault.every.max(1) == 0 {
+                    error = fault_error(name);
+                }
+            }
+        }
+        Ok((Duration::from_millis(fault.delay_ms), error))
+    })?;
+
+    // Sleep without holding the state, so other threads are not held up
+    thread::sleep(delay);
+    error.map_or(Ok(()), Err)
+}
+
+pub struct MockLibrary;
+impl StartupDiskTrait for MockLibrary {
+    fn is_supported(&self) -> bool {
+        true
+    }
+
+    fn access(&self, _operation: Operation) -> Access {
+        Access::Granted
+    }
+
+    fn authorize(&self, _operation: Operation) -> Result<()> {
+        Ok(())
+    }
+
+    fn nvram_device(&self) -> Result<String> {
+        Ok("mock".to_string())
+    }
+
+    fn current_part_uuid(&self) -> Option<Uuid> {
+        with_state(|state| Ok(state.current.map(|(_, part_uuid)| part_uuid)))
+            .ok()
+            .flatten()
+    }
+
+    fn get_boot_candidates(&self) -> Result<Vec<BootCandidate>> {
+        inject_fault(Operation::GetBootCandidates)?;
+        with_state(|state| Ok(state.candidates.iter().map(copy_candidate).collect()))
+    }
+
+    fn get_boot_volume(&self, next: bool) -> Result<BootCandidate> {
+        inject_fault(Operation::GetBootVolume)?;
+        with_state(|state| {
+            // Like the real NVRAM, the next boot volume falls back to the default one
+            let boot_volume = if next {
+                state.next.or(state.default)
+            } else {
+                state.default
+            };
+            let (vg_uuid, part_uuid) =
+                boot_volume.ok_or(Error::Bless(asahi_bless::Error::Parse))?;
+
+            Ok(BootCandidate {
+                vg_uuid,
+                volumes: Vec::new(),
+                part_uuid,
+            })
+        })
+    }
+
+    /// Writes without locking; the caller holds the NVRAM lock
+    fn set_boot_volume(&self, cand: &BootCandidate, next: bool) -> Result<()> {
+        inject_fault(Operation::SetBootVolume)?;
+        with_state(|state| {
+            if state.silent_write_failure {
+                return Ok(());
+            }
+            let boot_volume = Some((cand.vg_uuid, cand.part_uuid));
+            if next {
+                state.next = boot_volume;
+            } else {
+                state.default = boot_volume;
+            }
+            state.written = true;
+            Ok(())
+        })
+    }
+
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        inject_fault(Operation::ListBackups)?;
+        Ok(Vec::new())
+    }
+
+    fn restore_backup(&self, name: &str) -> Result<()> {
+        inject_fault(Operation::RestoreBackup)?;
+        Err(Error::Backup(format!("No backup named {name}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::startup_disk::lock::lock_nvram;
+    use crate::startup_disk::{set_and_verify, TEST_LOCK};
+    use std::sync::MutexGuard;
+
+    /// Every error a fault can inject
+    static FAULT_ERRORS: &[&str] = &[
+        "Parse",
+        "SectionTooBig",
+        "ApplyError",
+        "OutOfRange",
+        "Ambiguous",
+        "NvramReadError",
+        "DiskReadError",
+        "VolumeNotFound",
+        "Busy",
+        "Lock",
+    ];
+
+    /// Loads the dual-boot scenario with `faults` as `MOCK_FAULTS`
+    fn use_scenario(faults: &str, silent_write_failure: bool) -> MutexGuard<'static, ()> {
+        let guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::set_var(
+            "USE_MOCK_LIBRARY",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../res/scenarios/dual-boot.toml"
+            ),
+        );
+        env::set_var("MOCK_FAULTS", faults);
+        env::remove_var("MOCK_LATENCY_MS");
+        if silent_write_failure {
+            env::set_var("MOCK_SILENT_WRITE_FAILURE", "1");
+        } else {
+            env::remove_var("MOCK_SILENT_WRITE_FAILURE");
+        }
+        reload_mock();
+        guard
+    }
+
+    fn asahi_linux() -> BootCandidate {
+        MockLibrary
+            .get_boot_candidates()
+            .unwrap()
+            .into_iter()
+            .find(|cand| get_vg_name(&cand.volumes) == "Asahi Linux")
+            .unwrap()
+    }
+
+    fn call(operation: Operation, cand: &BootCandidate) -> Result<()> {
+        match operation {
+            Operation::GetBootCandidates => MockLibrary.get_boot_candidates().map(|_| ()),
+            Operation::GetBootVolume => MockLibrary.get_boot_volume(false).map(|_| ()),
+            Operation::SetBootVolume => MockLibrary.set_boot_volume(cand, false),
+            Operation::ListBackups => MockLibrary.list_backups().map(|_| ()),
+            Operation::RestoreBackup => MockLibrary.restore_backup("backup"),
+        }
+    }
+
+    #[test]
+    fn injected_faults_are_returned() {
+        for operation in Operation::ALL {
+            for error in FAULT_ERRORS {
+                let _guard = use_scenario("", false);
+                let cand = asahi_linux();
+                let method = operation.name();
+
+                env::set_var(
+                    "MOCK_FAULTS",
+                    format!("{method} = {{ error = \"{error}\" }}"),
+                );
+                reload_mock();
+                let expected = fault_error(error).unwrap();
+                match call(operation, &cand) {
+                    Err(e) => assert_eq!(e.to_string(), expected.to_string(), "{method}"),
+                    Ok(()) => panic!("{method} succeeded despite {error}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn faults_every_nth_call() {
+        let _guard = use_scenario(
+            r#"get_boot_volume = { error = "NvramReadError", every = 2 }"#,
+            false,
+        );
+        assert!(MockLibrary.get_boot_volume(false).is_ok());
+        assert!(MockLibrary.get_boot_volume(false).is_err());
+        assert!(MockLibrary.get_boot_volume(false).is_ok());
+    }
+
+    #[test]
+    fn invalid_faults_are_rejected() {
+        let _guard = use_scenario(r#"reboot = { error = "Parse" }"#, false);
+        assert!(MockLibrary.get_boot_candidates().is_err());
+
+        env::set_var("MOCK_FAULTS", r#"get_boot_volume = { error = "Panic" }"#);
+        reload_mock();
+        assert!(MockLibrary.get_boot_candidates().is_err());
+    }
+
+    #[test]
+    fn writes_are_verified() {
+        let _guard = use_scenario("", false);
+        let cand = asahi_linux();
+
+        set_and_verify(&MockLibrary, None, &cand, false).unwrap();
+        let boot_volume = MockLibrary.get_boot_volume(false).unwrap();
+        assert_eq!(boot_volume.vg_uuid, cand.vg_uuid);
+    }
+
+    #[test]
+    fn silent_write_failure_is_detected() {
+        let _guard = use_scenario("", true);
+        let cand = asahi_linux();
+
+        match set_and_verify(&MockLibrary, None, &cand, false) {
+            Err(Error::Verification {
+                rolled_back: true, ..
+            }) => {}
+            result => panic!("unexpected result: {result:?}"),
+        }
+        let boot_volume = MockLibrary.get_boot_volume(false).unwrap();
+        assert_ne!(boot_volume.vg_uuid, cand.vg_uuid);
+    }
+
+    #[test]
+    fn locked_nvram_is_not_written() {
+        let _guard = use_scenario("", false);
+        let cand = asahi_linux();
+
+        let _lock = lock_nvram(None).unwrap();
+        assert!(matches!(
+            set_and_verify(&MockLibrary, None, &cand, false),
+            Err(Error::Busy)
+        ));
+        let boot_volume = MockLibrary.get_boot_volume(false).unwrap();
+        assert_ne!(boot_volume.vg_uuid, cand.vg_uuid);
+    }
+}
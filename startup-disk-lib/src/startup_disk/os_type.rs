@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: MIT
+
+use asahi_bless::BootCandidate;
+use gettextrs::dgettext;
+
+use crate::startup_disk::i18n::GETTEXT_DOMAIN;
+
+/// Words that mark a volume group as the macOS stub of a Linux install
+static LINUX_NAMES: &[&str] = &[
+    "asahi", "linux", "fedora", "ubuntu", "debian", "gentoo", "nixos",
+];
+
+/// Operating system a boot candidate starts up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OsType {
+    MacOs,
+    /// The macOS stub the Asahi installer creates for a Linux install
+    AsahiLinux,
+    Recovery,
+    Unknown,
+}
+
+impl OsType {
+    /// Classifies a boot candidate from its volume names and roles
+    ///
+    /// asahi-bless only reports volume names and whether each volume is the
+    /// system volume, so the stub installs are recognized by the names the
+    /// Asahi installer gives them. The macOS version is only recorded inside
+    /// the APFS file system, which cannot be read from Linux, so macOS
+    /// installs are not told apart by version.
+    pub fn classify(cand: &BootCandidate) -> OsType {
+        let names: Vec<String> = cand.volumes.iter().map(|v| v.name.to_lowercase()).collect();
+
+        if names
+            .iter()
+            .any(|name| LINUX_NAMES.iter().any(|linux| name.contains(linux)))
+        {
+            OsType::AsahiLinux
+        } else if names.iter().any(|name| name.contains("recovery")) {
+            OsType::Recovery
+        } else if cand.volumes.iter().any(|v| v.is_system) {
+            OsType::MacOs
+        } else {
+            OsType::Unknown
+        }
+    }
+
+    /// Returns the identifier used in machine-readable output
+    pub fn id(self) -> &'static str {
+        match self {
+            OsType::MacOs => "macos",
+            OsType::AsahiLinux => "asahi-linux",
+            OsType::Recovery => "recovery",
+            OsType::Unknown => "unknown",
+        }
+    }
+
+    /// Returns the translated name of the operating system
+    pub fn label(self) -> String {
+        match self {
+            OsType::MacOs => "macOS".to_string(),
+            OsType::AsahiLinux => "Asahi Linux".to_string(),
+            OsType::Recovery => dgettext(GETTEXT_DOMAIN, "Recovery"),
+            OsType::Unknown => dgettext(GETTEXT_DOMAIN, "Unknown System"),
+        }
+    }
+
+    pub fn icon_name(self) -> &'static str {
+        match self {
+            OsType::MacOs => "drive-harddisk-system",
+            // The distribution logo
+            OsType::AsahiLinux => "start-here",
+            OsType::Recovery => "emblem-system",
+            OsType::Unknown => "drive-harddisk",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asahi_bless::Volume;
+    use uuid::Uuid;
+
+    fn candidate(volumes: &[(&str, bool)]) -> BootCandidate {
+        BootCandidate {
+            vg_uuid: Uuid::from_u128(1),
+            part_uuid: Uuid::from_u128(2),
+            volumes: volumes
+                .iter()
+                .map(|&(name, is_system)| Volume {
+                    name: name.to_string(),
+                    is_system,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn macos() {
+        let cand = candidate(&[("Macintosh HD", true), ("Macintosh HD - Data", false)]);
+        assert_eq!(OsType::classify(&cand), OsType::MacOs);
+    }
+
+    #[test]
+    fn asahi_stub() {
+        for name in ["Asahi Linux", "Fedora Linux with KDE Plasma", "ASAHI"] {
+            let cand = candidate(&[(name, true)]);
+            assert_eq!(OsType::classify(&cand), OsType::AsahiLinux, "{name}");
+        }
+    }
+
+    #[test]
+    fn recovery() {
+        let cand = candidate(&[("Recovery", true)]);
+        assert_eq!(OsType::classify(&cand), OsType::Recovery);
+    }
+
+    #[test]
+    fn unknown() {
+        let cand = candidate(&[("Shared", false)]);
+        assert_eq!(OsType::classify(&cand), OsType::Unknown);
+        assert_eq!(OsType::classify(&candidate(&[])), OsType::Unknown);
+    }
+
+    #[test]
+    fn linux_names_take_precedence_over_recovery() {
+        let cand = candidate(&[("Fedora Linux", true), ("Recovery", false)]);
+        assert_eq!(OsType::classify(&cand), OsType::AsahiLinux);
+    }
+}